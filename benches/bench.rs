@@ -1,6 +1,8 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use serde::{Deserialize, Serialize};
+use serde_bencoded::{Serializer, SerializerOptions, Value};
 use serde_bytes::ByteBuf;
+use std::collections::{BTreeMap, HashMap};
 #[derive(Debug, Serialize, Deserialize)]
 struct Info {
     #[serde(rename = "piece length")]
@@ -95,5 +97,38 @@ fn criterion_benchmark2(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark, criterion_benchmark2);
+/// Sorted dictionaries nested deep and with many keys
+fn criterion_benchmark3(c: &mut Criterion) {
+    let mut nested = Value::Integer(0);
+    for depth in 0..64 {
+        let mut dict = BTreeMap::new();
+        dict.insert(b"depth".to_vec(), Value::Integer(depth));
+        dict.insert(b"name".to_vec(), Value::Bytes(b"nested".to_vec()));
+        dict.insert(b"next".to_vec(), nested);
+        nested = Value::Dict(dict);
+    }
+    c.bench_function("serialize nested dictionaries", |b| {
+        b.iter(|| serde_bencoded::to_vec(black_box(&nested)))
+    });
+
+    let wide: HashMap<String, u64> = (0..1000).map(|i| (format!("key{}", i), i)).collect();
+    c.bench_function("serialize wide dictionary", |b| {
+        b.iter(|| serde_bencoded::to_vec(black_box(&wide)))
+    });
+    let options = SerializerOptions { sort_keys: false };
+    c.bench_function("serialize wide dictionary unsorted", |b| {
+        b.iter(|| {
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            black_box(&wide).serialize(&mut ser).unwrap();
+            ser.into_inner()
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    criterion_benchmark2,
+    criterion_benchmark3
+);
 criterion_main!(benches);
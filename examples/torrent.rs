@@ -1,23 +1,18 @@
 use serde::{Deserialize, Serialize};
-use serde_bencoded::from_bytes;
+use serde_bencoded::from_reader;
 use serde_bytes::ByteBuf;
-use std::io::Read;
+use std::io::BufReader;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let bytes = if atty::is(atty::Stream::Stdin) {
+    let info: MetaInfo = if atty::is(atty::Stream::Stdin) {
         let second_arg = std::env::args_os()
             .nth(1)
             .ok_or("missing `path` argument".to_string())?;
-        let mut file = std::fs::File::open(second_arg)?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf)?;
-        buf
+        let file = std::fs::File::open(second_arg)?;
+        from_reader(BufReader::new(file))?
     } else {
-        let mut buf = Vec::new();
-        std::io::stdin().lock().read_to_end(&mut buf)?;
-        buf
+        from_reader(std::io::stdin().lock())?
     };
-    let info: MetaInfo = from_bytes(&bytes)?;
     println!("announce: {}", info.announce);
     println!("announce-list: {:?}", info.announce_list);
     println!("creation date: {:?}", info.creation_date);
@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use serde::de::IgnoredAny;
+use serde_bencoded::{from_bytes, from_bytes_auto, from_reader, Value, ValueRef};
+
+mod teststruct;
+use teststruct::*;
+
+// Repeats fuzzer input to build deeply nested documents,
+// parsing them must fail with an error instead of overflowing the stack.
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let input: Vec<u8> = data.iter().copied().cycle().take(data.len().max(1 << 16)).collect();
+    from_bytes::<Value>(&input).ok();
+    from_bytes::<ValueRef>(&input).ok();
+    from_bytes::<IgnoredAny>(&input).ok();
+    from_bytes_auto::<Value>(&input).ok();
+    from_bytes::<TestStruct>(&input).ok();
+    from_reader::<_, Value>(input.as_slice()).ok();
+});
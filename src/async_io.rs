@@ -0,0 +1,332 @@
+use std::{
+    future::poll_fn,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{error::SerResult, to_writer, DeError, DeResult, Progress, PushParser};
+
+/// Deserializes bencode from an async `reader`, like [`from_reader`](crate::from_reader).
+///
+/// The input is read in chunks until the value is complete, then the value
+/// is deserialized with [`from_bytes`](crate::from_bytes). It returns as
+/// soon as the value ends, without waiting for the reader to end, so the
+/// reader can be a socket that stays open. Bytes after the value that came
+/// in the same chunk are rejected, read values that follow each other with
+/// [`from_async_reader_with`] or `BencodeCodec` instead.
+/// # Examples
+/** ```
+# use serde_bencoded::from_async_reader;
+# #[tokio::main(flavor = "current_thread")]
+# async fn main() -> Result<(), Box<dyn std::error::Error>>{
+let reader: &[u8] = b"l4:spami42ee";
+assert_eq!(from_async_reader::<_, (String, u8)>(reader).await?, ("spam".to_string(), 42));
+# Ok(())
+# }
+```*/
+pub async fn from_async_reader<R, T>(reader: R) -> DeResult<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut parser = PushParser::new();
+    let (value, end) = read_value(reader, &mut parser).await?;
+    match parser.buffered().first() {
+        Some(&byte) => Err(DeError::SyntaxError(byte, None).at(end)),
+        None => Ok(value),
+    }
+}
+
+/// Reads one value from an async `reader` with the settings of `parser`.
+///
+/// [`PushParser::max_depth`] and [`PushParser::limits`] are checked as the
+/// input arrives, so a value can't make the buffer grow past them. Bytes
+/// the parser has buffered are read first, bytes read after the value stay
+/// in [`PushParser::buffered`] for the next call.
+/// # Examples
+/** ```
+# use serde_bencoded::{from_async_reader_with, DeError, Limits, PushParser, Value};
+# #[tokio::main(flavor = "current_thread")]
+# async fn main() -> Result<(), Box<dyn std::error::Error>>{
+let mut reader: &[u8] = b"i1ei2e";
+let mut parser = PushParser::<u8>::new();
+assert_eq!(from_async_reader_with(&mut reader, &mut parser).await?, 1);
+assert_eq!(from_async_reader_with(&mut reader, &mut parser).await?, 2);
+
+let reader: &[u8] = b"l4:spam4:eggse";
+let mut parser = PushParser::new().limits(Limits { max_size: 8, ..Limits::default() });
+let err = from_async_reader_with::<_, Value>(reader, &mut parser).await.unwrap_err();
+assert_eq!(err.kind(), &DeError::DocumentTooLarge(8));
+# Ok(())
+# }
+```*/
+pub async fn from_async_reader_with<R, T>(reader: R, parser: &mut PushParser<T>) -> DeResult<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    read_value(reader, parser).await.map(|(value, _)| value)
+}
+
+/// Reads a value, returns it with the number of bytes it took
+async fn read_value<R, T>(mut reader: R, parser: &mut PushParser<T>) -> DeResult<(T, usize)>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut chunk = [0; 8 * 1024];
+    let mut total = parser.buffered().len();
+    // the first call scans the bytes that are already buffered
+    let mut read = 0;
+    loop {
+        if let Progress::Done(value) = parser.feed(&chunk[..read])? {
+            return Ok((value, total - parser.buffered().len()));
+        }
+        read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(DeError::UnexpectedEof.at(parser.buffered().len()));
+        }
+        total += read;
+    }
+}
+
+/// Size of the chunks [`to_async_writer`] passes to the writer
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Serializes `value` to an async `writer` with the same rules as
+/// [`to_writer`](crate::to_writer), then flushes it.
+///
+/// The output is passed to the writer in chunks of 8 KiB as it's encoded,
+/// so while the writer keeps up only one chunk is buffered. A dictionary
+/// with sorted keys is written once it is complete, see
+/// [`SerializerOptions`](crate::SerializerOptions).
+/// [`Serializer`](crate::Serializer) can't wait for the writer, so once the
+/// writer isn't ready the rest of the output is kept until the value is
+/// encoded and then written chunk by chunk.
+/// # Examples
+/** ```
+# use serde_bencoded::to_async_writer;
+# #[tokio::main(flavor = "current_thread")]
+# async fn main() -> Result<(), Box<dyn std::error::Error>>{
+let mut buf = Vec::new();
+to_async_writer(&("spam", 42), &mut buf).await?;
+assert_eq!(buf, b"l4:spami42ee");
+# Ok(())
+# }
+```*/
+pub async fn to_async_writer<T, W>(value: &T, mut writer: W) -> SerResult<()>
+where
+    T: Serialize,
+    W: AsyncWrite + Unpin,
+{
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    let mut rest = Vec::new();
+    poll_fn(|cx| {
+        let mut sink = Sink {
+            writer: Pin::new(&mut writer),
+            cx,
+            chunk: &mut chunk,
+            rest: &mut rest,
+        };
+        Poll::Ready(to_writer(value, &mut sink))
+    })
+    .await?;
+    rest.append(&mut chunk);
+    for chunk in rest.chunks(CHUNK_SIZE) {
+        writer.write_all(chunk).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Passes full chunks to an async writer while it's ready, keeps the
+/// output in `rest` after it isn't
+struct Sink<'a, 'b, W> {
+    writer: Pin<&'a mut W>,
+    cx: &'a mut Context<'b>,
+    chunk: &'a mut Vec<u8>,
+    rest: &'a mut Vec<u8>,
+}
+
+impl<W: AsyncWrite> io::Write for Sink<'_, '_, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if !self.rest.is_empty() {
+            self.rest.extend_from_slice(data);
+            return Ok(data.len());
+        }
+        let n = data.len().min(CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&data[..n]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(n)
+    }
+
+    /// Writes as much of the chunk as the writer takes without waiting,
+    /// moves the remainder to `rest` if it isn't ready
+    fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        while written < self.chunk.len() {
+            match self
+                .writer
+                .as_mut()
+                .poll_write(self.cx, &self.chunk[written..])
+            {
+                Poll::Ready(Ok(0)) => return Err(io::ErrorKind::WriteZero.into()),
+                Poll::Ready(Ok(n)) => written += n,
+                Poll::Ready(Err(e)) => return Err(e),
+                Poll::Pending => {
+                    self.rest.extend_from_slice(&self.chunk[written..]);
+                    break;
+                }
+            }
+        }
+        self.chunk.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SerError, Value};
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn duplex() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut client, server) = tokio::io::duplex(16);
+        let mut map = BTreeMap::new();
+        map.insert("pieces", Value::Bytes(vec![7; 100]));
+        map.insert("name", Value::Bytes(b"x".to_vec()));
+        map.insert("length", Value::Integer(100));
+        let expected = map.clone();
+        let writer = tokio::spawn(async move {
+            to_async_writer(&map, &mut client).await?;
+            drop(client);
+            Ok::<_, SerError>(())
+        });
+        let read: BTreeMap<String, Value> = from_async_reader(server).await?;
+        writer.await??;
+        assert_eq!(read.len(), 3);
+        assert_eq!(read["pieces"], expected["pieces"]);
+        assert_eq!(read["length"], Value::Integer(100));
+        Ok(())
+    }
+
+    /// Accepts everything, records the size of each write
+    #[derive(Default)]
+    struct Recorder {
+        writes: Vec<usize>,
+    }
+
+    impl AsyncWrite for Recorder {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.writes.push(buf.len());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn chunks() -> SerResult<()> {
+        let list = vec![1_000_000u32; 10_000];
+        let mut recorder = Recorder::default();
+        to_async_writer(&list, &mut recorder).await?;
+        assert_eq!(recorder.writes.iter().sum::<usize>(), 2 + 9 * 10_000);
+        assert!(recorder.writes.len() > 10);
+        assert!(recorder.writes.iter().all(|&len| len <= CHUNK_SIZE));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn slow_writer() -> Result<(), Box<dyn std::error::Error>> {
+        let (client, mut server) = tokio::io::duplex(100);
+        let list = vec![b'x'; 3 * CHUNK_SIZE];
+        let writer = tokio::spawn(async move {
+            let mut client = client;
+            to_async_writer(&serde_bytes::ByteBuf::from(list), &mut client).await
+        });
+        let mut read = Vec::new();
+        server.read_to_end(&mut read).await?;
+        writer.await??;
+        assert_eq!(&read[..6], b"24576:");
+        assert_eq!(read.len(), 6 + 3 * CHUNK_SIZE);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn limits() {
+        let mut parser = PushParser::new().max_depth(1);
+        let err = from_async_reader_with::<_, Value>(&b"lli1eee"[..], &mut parser)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), &DeError::DepthLimitExceeded(1));
+        assert_eq!(err.position(), Some(1));
+        let mut parser = PushParser::new();
+        assert_eq!(parser.feed(b"l4:sp").unwrap(), Progress::NeedMoreData);
+        let value: Vec<String> = from_async_reader_with(&b"ame"[..], &mut parser)
+            .await
+            .unwrap();
+        assert_eq!(value, ["spam"]);
+    }
+
+    #[tokio::test]
+    async fn values_in_one_read() -> DeResult<()> {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"l4:spamei42e").await?;
+        let mut parser = PushParser::new();
+        let value: Value = from_async_reader_with(&mut server, &mut parser).await?;
+        assert_eq!(value, Value::List(vec![Value::Bytes(b"spam".to_vec())]));
+        assert_eq!(parser.buffered(), b"i42e");
+        let value = from_async_reader_with(&mut server, &mut parser).await?;
+        assert_eq!(value, Value::Integer(42));
+        assert!(parser.buffered().is_empty());
+        drop(client);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_reader() -> DeResult<()> {
+        let (mut client, server) = tokio::io::duplex(16);
+        client.write_all(b"l4:spame").await?;
+        // the value is returned while the writer is still open
+        let value: Vec<String> = from_async_reader(server).await?;
+        assert_eq!(value, ["spam"]);
+        drop(client);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let err = from_async_reader::<_, Value>(&b"li1e"[..])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), &DeError::UnexpectedEof);
+        assert_eq!(err.position(), Some(4));
+        let err = from_async_reader::<_, Value>(&b"i1ei2e"[..])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), &DeError::SyntaxError(b'i', None));
+        assert_eq!(err.position(), Some(3));
+        let err = from_async_reader::<_, Value>(&b"lxe"[..])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), &DeError::SyntaxError(b'x', None));
+        assert!(to_async_writer(&1.0, Vec::new()).await.is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::marker::PhantomData;
+
+use bytes::{BufMut, BytesMut};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{de::Scanner, to_writer, DeError, Limits, SerError};
+
+/// [`Decoder`] and [`Encoder`] of whole bencoded values, to use with
+/// [`Framed`](tokio_util::codec::Framed).
+///
+/// A frame is one value, no length prefix or separator is needed. Its end
+/// is found as bytes arrive, like with [`PushParser`](crate::PushParser),
+/// then it's deserialized like with [`from_bytes`](crate::from_bytes).
+/// Values are encoded with [`to_writer`], nothing is written for a value
+/// that fails to serialize. Set [`Limits`] on input from the
+/// network, otherwise a peer can make the read buffer grow without bound.
+/// # Examples
+/** ```
+# use serde_bencoded::{BencodeCodec, Value};
+# use futures::{SinkExt, StreamExt};
+# use tokio_util::codec::Framed;
+# #[tokio::main(flavor = "current_thread")]
+# async fn main() -> Result<(), Box<dyn std::error::Error>>{
+let (client, server) = tokio::io::duplex(64);
+let mut client = Framed::new(client, BencodeCodec::<Value>::new());
+let mut server = Framed::new(server, BencodeCodec::<Value>::new());
+client.send(Value::Integer(1)).await?;
+assert_eq!(server.next().await.transpose()?, Some(Value::Integer(1)));
+# Ok(())
+# }
+```*/
+pub struct BencodeCodec<T> {
+    scanner: Scanner,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T> Default for BencodeCodec<T> {
+    fn default() -> Self {
+        BencodeCodec::new()
+    }
+}
+
+impl<T> BencodeCodec<T> {
+    pub fn new() -> Self {
+        BencodeCodec {
+            scanner: Scanner::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// See [`Deserializer::strict`](crate::Deserializer::strict)
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.scanner.strict = strict;
+        self
+    }
+
+    /// See [`Deserializer::max_depth`](crate::Deserializer::max_depth)
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.scanner.max_depth = max_depth;
+        self
+    }
+
+    /// See [`PushParser::limits`](crate::PushParser::limits), they apply
+    /// to each frame
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.scanner.limits = limits;
+        self
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for BencodeCodec<T> {
+    type Item = T;
+    type Error = DeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, DeError> {
+        match self.scanner.scan(src)? {
+            Some(end) => {
+                let frame = src.split_to(end);
+                self.scanner.deserialize(&frame).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T: Serialize> Encoder<T> for BencodeCodec<T> {
+    type Error = SerError;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), SerError> {
+        let len = dst.len();
+        let ret = to_writer(&item, dst.writer());
+        // half of a frame would corrupt the ones after it
+        if ret.is_err() {
+            dst.truncate(len);
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SerResult, Value};
+    use futures::{SinkExt, StreamExt};
+    use serde::Deserialize;
+    use tokio_util::codec::{Framed, FramedRead};
+
+    /// KRPC message of BEP 5
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Query {
+        t: String,
+        y: String,
+        q: String,
+        a: Args,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Args {
+        id: String,
+    }
+
+    #[tokio::test]
+    async fn framed() -> Result<(), Box<dyn std::error::Error>> {
+        let (client, server) = tokio::io::duplex(8);
+        let mut client = Framed::new(client, BencodeCodec::<Query>::new());
+        let mut server = Framed::new(server, BencodeCodec::<Query>::new());
+        let ping = |t: &str| Query {
+            t: t.to_string(),
+            y: "q".to_string(),
+            q: "ping".to_string(),
+            a: Args {
+                id: "abcdefghij0123456789".to_string(),
+            },
+        };
+        let sender = tokio::spawn(async move {
+            for t in ["aa", "bb", "cc"] {
+                client.send(ping(t)).await?;
+            }
+            SerResult::Ok(())
+        });
+        for t in ["aa", "bb", "cc"] {
+            assert_eq!(server.next().await.transpose()?, Some(ping(t)));
+        }
+        sender.await??;
+        assert!(server.next().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let input: &[u8] = b"i1eli2ee3:abcx";
+        let mut frames = FramedRead::new(input, BencodeCodec::<Value>::new());
+        assert_eq!(frames.next().await, Some(Ok(Value::Integer(1))));
+        assert_eq!(
+            frames.next().await,
+            Some(Ok(Value::List(vec![Value::Integer(2)])))
+        );
+        assert_eq!(frames.next().await, Some(Ok(Value::Bytes(b"abc".to_vec()))));
+        let err = frames.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), &DeError::SyntaxError(b'x', None));
+
+        let limits = Limits {
+            max_size: 8,
+            ..Limits::default()
+        };
+        let input: &[u8] = b"i1e20:abc";
+        let mut frames = FramedRead::new(input, BencodeCodec::<Value>::new().limits(limits));
+        assert_eq!(frames.next().await, Some(Ok(Value::Integer(1))));
+        let err = frames.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), &DeError::DocumentTooLarge(8));
+
+        let mut frames = FramedRead::new(&b"li1e"[..], BencodeCodec::<Value>::new());
+        assert!(frames.next().await.unwrap().is_err());
+    }
+
+    #[test]
+    fn encode_error() -> SerResult<()> {
+        let mut codec = BencodeCodec::<Vec<f64>>::new();
+        let mut dst = BytesMut::new();
+        codec.encode(vec![], &mut dst)?;
+        assert_eq!(
+            codec.encode(vec![1.0], &mut dst),
+            Err(SerError::FloatingPointNotSupported)
+        );
+        assert_eq!(&dst[..], b"le");
+        codec.encode(vec![], &mut dst)?;
+        assert_eq!(&dst[..], b"lele");
+        Ok(())
+    }
+}
@@ -1,22 +1,39 @@
 use std::marker::PhantomData;
 
-use crate::error::{DeError as Error, DeResult as Result};
+use crate::error::{DeError as Error, DeResult as Result, PathSegment};
 use serde::{
-    de::{self, DeserializeSeed, Visitor},
+    de::{self, DeserializeOwned, DeserializeSeed, Visitor},
     Deserialize,
 };
 
+mod push;
+pub mod read;
+mod stream;
+mod tokenizer;
+
+#[cfg(feature = "tokio-codec")]
+pub(crate) use push::Scanner;
+pub use push::{Progress, PushParser};
+use read::{IoRead, Read, Reference, SliceRead};
+pub use stream::StreamDeserializer;
+pub use tokenizer::{Token, Tokenizer};
+
 pub struct Auto;
 impl private::Sealed for Auto {}
 impl Behaviour for Auto {
-    fn deserialize_byte_string<'de, Te: Behaviour, V: Visitor<'de>>(
-        de: &mut Deserializer<'de, Te>,
+    fn visit_byte_string<'de, V: Visitor<'de>>(
+        bytes: Reference<'de, '_, [u8]>,
         visitor: V,
     ) -> Result<V::Value> {
-        let bytes = de.parse_byte_string()?;
-        match std::str::from_utf8(bytes).ok() {
-            Some(s) => visitor.visit_borrowed_str(s),
-            None => visitor.visit_borrowed_bytes(bytes),
+        match bytes {
+            Reference::Borrowed(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(b),
+            },
+            Reference::Copied(b) => match std::str::from_utf8(b) {
+                Ok(s) => visitor.visit_str(s),
+                Err(_) => visitor.visit_bytes(b),
+            },
         }
     }
 }
@@ -24,57 +41,228 @@ impl Behaviour for Auto {
 pub struct Simple;
 impl private::Sealed for Simple {}
 impl Behaviour for Simple {
-    fn deserialize_byte_string<'de, Te: Behaviour, V: Visitor<'de>>(
-        de: &mut Deserializer<'de, Te>,
+    fn visit_byte_string<'de, V: Visitor<'de>>(
+        bytes: Reference<'de, '_, [u8]>,
         visitor: V,
     ) -> Result<V::Value> {
-        de::Deserializer::deserialize_bytes(de, visitor)
+        visit_bytes(bytes, visitor)
     }
 }
 mod private {
     pub trait Sealed {}
 }
 pub trait Behaviour: private::Sealed {
-    fn deserialize_byte_string<'de, Te: Behaviour, V: Visitor<'de>>(
-        // &self,
-        de: &mut Deserializer<'de, Te>,
+    fn visit_byte_string<'de, V: Visitor<'de>>(
+        bytes: Reference<'de, '_, [u8]>,
         visitor: V,
     ) -> Result<V::Value>;
 }
 
-pub struct Deserializer<'de, T: Behaviour> {
-    input: &'de [u8],
-    _marker: PhantomData<T>,
+fn visit_bytes<'de, V: Visitor<'de>>(
+    bytes: Reference<'de, '_, [u8]>,
+    visitor: V,
+) -> Result<V::Value> {
+    match bytes {
+        Reference::Borrowed(b) => visitor.visit_borrowed_bytes(b),
+        Reference::Copied(b) => visitor.visit_bytes(b),
+    }
+}
+
+fn visit_str<'de, V: Visitor<'de>>(s: Reference<'de, '_, str>, visitor: V) -> Result<V::Value> {
+    match s {
+        Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
+        Reference::Copied(s) => visitor.visit_str(s),
+    }
+}
+
+fn to_str<'de, 's>(bytes: Reference<'de, 's, [u8]>) -> Result<Reference<'de, 's, str>> {
+    Ok(match bytes {
+        Reference::Borrowed(b) => Reference::Borrowed(std::str::from_utf8(b)?),
+        Reference::Copied(b) => Reference::Copied(std::str::from_utf8(b)?),
+    })
+}
+
+pub struct Deserializer<'de, T: Behaviour, R: Read<'de> = SliceRead<'de>> {
+    read: R,
+    /// Buffer for byte strings that can't be borrowed from `read`
+    scratch: Vec<u8>,
+    strict: bool,
+    /// Number of lists and dictionaries the parser is inside of
+    depth: usize,
+    max_depth: usize,
+    limits: Limits,
+    _marker: PhantomData<(&'de (), T)>,
 }
 
 impl<'de> Deserializer<'de, Simple> {
     pub fn from_bytes(input: &'de [u8]) -> Self {
-        Deserializer {
-            input,
-            _marker: PhantomData,
-        }
+        Deserializer::new(SliceRead::new(input))
     }
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(input: &'de str) -> Self {
-        Deserializer {
-            input: input.as_bytes(),
-            _marker: PhantomData,
-        }
+        Deserializer::new(SliceRead::new(input.as_bytes()))
     }
 }
 
 impl<'de> Deserializer<'de, Auto> {
     pub fn from_bytes_auto(input: &'de [u8]) -> Self {
-        Deserializer {
-            input,
-            _marker: PhantomData,
-        }
+        Deserializer::new(SliceRead::new(input))
     }
     pub fn from_str_auto(input: &'de str) -> Self {
+        Deserializer::new(SliceRead::new(input.as_bytes()))
+    }
+}
+
+impl<'de, T: Behaviour> Deserializer<'de, T> {
+    /// Returns the input that is not consumed yet
+    /// # Examples
+    /** ```
+    # use serde_bencoded::Deserializer;
+    # use serde::Deserialize;
+    let mut de = Deserializer::from_bytes(b"4:spamtail");
+    assert_eq!(<&str>::deserialize(&mut de), Ok("spam"));
+    assert_eq!(de.remaining(), b"tail");
+    ```*/
+    pub fn remaining(&self) -> &'de [u8] {
+        self.read.remaining()
+    }
+}
+
+impl<'de, R: std::io::Read> Deserializer<'de, Simple, IoRead<R>> {
+    /// Creates a deserializer that reads from `reader` as needed, see
+    /// [`IoRead`].
+    pub fn from_reader(reader: R) -> Self {
+        Deserializer::new(IoRead::new(reader))
+    }
+}
+
+impl<'de, T: Behaviour, R: Read<'de>> Deserializer<'de, T, R> {
+    fn new(read: R) -> Self {
         Deserializer {
-            input: input.as_bytes(),
+            read,
+            scratch: Vec::new(),
+            strict: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
             _marker: PhantomData,
         }
     }
+
+    /// Enables strict mode, in which only canonical bencode is accepted:
+    /// - integers and byte string lengths without leading zeros or `+` sign;
+    /// - no `i-0e`;
+    /// - dictionary keys are unique and sorted as raw bytes.
+    ///
+    /// Use it when the input must re-encode to exactly the same bytes,
+    /// for example to compute an info hash.
+    /// # Examples
+    /** ```
+    # use serde_bencoded::{DeError, Deserializer};
+    # use serde::Deserialize;
+    let mut de = Deserializer::from_bytes(b"i03e").strict(true);
+    assert_eq!(u32::deserialize(&mut de).unwrap_err().kind(), &DeError::LeadingZero);
+    ```*/
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets how deep lists and dictionaries can be nested, deeper input
+    /// fails with [`DeError::DepthLimitExceeded`](Error::DepthLimitExceeded)
+    /// instead of overflowing the stack. Defaults to [`DEFAULT_MAX_DEPTH`].
+    /// # Examples
+    /** ```
+    # use serde_bencoded::{DeError, Deserializer, Value};
+    # use serde::Deserialize;
+    let mut de = Deserializer::from_bytes(b"llleee").max_depth(2);
+    let err = Value::deserialize(&mut de).unwrap_err();
+    assert_eq!(err.kind(), &DeError::DepthLimitExceeded(2));
+    assert_eq!(err.position(), Some(2));
+    ```*/
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets [`Limits`] on the size of the input, there are none by default.
+    /// # Examples
+    /** ```
+    # use serde_bencoded::{DeError, Deserializer, Limits};
+    # use serde::Deserialize;
+    let limits = Limits {
+        max_bytes_len: 4,
+        ..Limits::default()
+    };
+    let mut de = Deserializer::from_bytes(b"l5:helloe").limits(limits);
+    let err = Vec::<String>::deserialize(&mut de).unwrap_err();
+    assert_eq!(err.kind(), &DeError::ByteStringTooLong(4));
+    assert_eq!(err.position(), Some(1));
+    ```*/
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.read.set_max_size(limits.max_size);
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the number of bytes consumed so far
+    pub fn position(&self) -> usize {
+        self.read.position()
+    }
+
+    /// Checks that the whole input was consumed, call it after deserializing
+    /// a value unless bytes after it are expected.
+    /// # Examples
+    /** ```
+    # use serde_bencoded::{DeError, Deserializer};
+    # use serde::Deserialize;
+    let mut de = Deserializer::from_bytes(b"i1ei2e");
+    assert_eq!(u8::deserialize(&mut de), Ok(1));
+    assert_eq!(de.end().unwrap_err().kind(), &DeError::SyntaxError(b'i', None));
+    ```*/
+    pub fn end(&mut self) -> Result<()> {
+        match self.read.peek()? {
+            None => Ok(()),
+            Some(next) => Err(Error::SyntaxError(next, None).at(self.position())),
+        }
+    }
+
+    /// Turns the deserializer into an iterator over values that follow
+    /// each other in the input, see [`StreamDeserializer`].
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<V: Deserialize<'de>>(self) -> StreamDeserializer<'de, V, T, R> {
+        StreamDeserializer::new(self)
+    }
+}
+
+/// Default for [`Deserializer::max_depth`]
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Limits on the size of the input, see [`Deserializer::limits`].
+///
+/// Input that exceeds them fails with the matching [`DeError`](Error)
+/// before anything is allocated for it. Defaults to no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum length of a byte string, see
+    /// [`DeError::ByteStringTooLong`](Error::ByteStringTooLong)
+    pub max_bytes_len: usize,
+    /// Maximum number of elements in a list or entries in a dictionary, see
+    /// [`DeError::TooManyElements`](Error::TooManyElements)
+    pub max_elements: usize,
+    /// Maximum number of bytes read from the input, see
+    /// [`DeError::DocumentTooLarge`](Error::DocumentTooLarge)
+    pub max_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_bytes_len: usize::MAX,
+            max_elements: usize::MAX,
+            max_size: usize::MAX,
+        }
+    }
 }
 
 /// Deserializes bencoded `&str` to rust's value.
@@ -113,16 +301,34 @@ fn _from_slice<'a, T, B: Behaviour>(slice: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::<B> {
-        input: slice,
-        _marker: PhantomData,
-    };
-    let t = T::deserialize(&mut deserializer)?;
-    if deserializer.input.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::SyntaxError(deserializer.input[0], None))
+    _from_deserializer(
+        &mut Deserializer::<B>::new(SliceRead::new(slice)),
+        Trailing::Reject,
+    )
+}
+
+/// What to do with bytes after the value
+enum Trailing {
+    /// Fail with [`Error::SyntaxError`]
+    Reject,
+    /// Leave them in the deserializer
+    Allow,
+}
+
+fn _from_deserializer<'a, T, B: Behaviour, R: Read<'a>>(
+    deserializer: &mut Deserializer<'a, B, R>,
+    trailing: Trailing,
+) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let start = deserializer.position();
+    let t = T::deserialize(&mut *deserializer).map_err(|e| e.at(start))?;
+    match trailing {
+        Trailing::Reject => deserializer.end()?,
+        Trailing::Allow => {}
     }
+    Ok(t)
 }
 
 /// Deserializes bencoded bytes to rust's value.
@@ -156,66 +362,186 @@ where
     _from_slice::<T, Auto>(b)
 }
 
-impl<'de, T: Behaviour> Deserializer<'de, T> {
-    fn peek_next(&self) -> Result<u8> {
-        self.input.first().copied().ok_or(Error::UnexpectedEof)
+/// The same as [`from_bytes`] but rejects input that is not canonical
+/// bencode. See [`Deserializer::strict`].
+/// # Examples
+/** ```
+# use serde_bencoded::{from_bytes_strict, DeError};
+# use std::collections::BTreeMap;
+let err = from_bytes_strict::<i64>(b"i-0e").unwrap_err();
+assert_eq!(err.kind(), &DeError::NegativeZero);
+let err = from_bytes_strict::<BTreeMap<&str, i64>>(b"d1:bi1e1:ai2ee").unwrap_err();
+assert_eq!(err.kind(), &DeError::UnsortedKey(b"a".to_vec()));
+assert_eq!(err.position(), Some(7));
+```*/
+pub fn from_bytes_strict<'a, T>(b: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    _from_deserializer(
+        &mut Deserializer::from_bytes(b).strict(true),
+        Trailing::Reject,
+    )
+}
+
+/// Deserializes a value from the start of `b` and returns it with the bytes
+/// that follow it, which [`from_bytes`] would reject.
+/// # Examples
+/// A [BEP 9](https://www.bittorrent.org/beps/bep_0009.html) data message
+/// is a dictionary followed by a piece of metadata.
+/** ```
+# use serde_bencoded::from_bytes_prefix;
+# use serde::Deserialize;
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+#[derive(Deserialize)]
+struct Data {
+    msg_type: u8,
+    piece: u32,
+    total_size: u32,
+}
+let message = b"d8:msg_typei1e5:piecei0e10:total_sizei5eed4:infoe";
+let (header, piece) = from_bytes_prefix::<Data>(message)?;
+assert_eq!((header.msg_type, header.piece, header.total_size), (1, 0, 5));
+assert_eq!(piece, b"d4:infoe");
+# Ok(())
+# }
+```*/
+pub fn from_bytes_prefix<'a, T>(b: &'a [u8]) -> Result<(T, &'a [u8])>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_bytes(b);
+    let t = _from_deserializer(&mut deserializer, Trailing::Allow)?;
+    Ok((t, deserializer.remaining()))
+}
+
+/// Deserializes bencode from `reader` to rust's value.
+///
+/// The input is decoded while it is read, byte strings are copied to a
+/// reusable buffer, so the whole input is never held in memory. The reader
+/// must end right after the value. Wrap unbuffered readers in
+/// [`BufReader`](std::io::BufReader), see [`IoRead`].
+/// # Examples
+/** ```
+# use serde_bencoded::from_reader;
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let reader = std::io::Cursor::new(b"l4:spami42ee");
+assert_eq!(from_reader::<_, (String, u8)>(reader)?, ("spam".to_string(), 42));
+# Ok(())
+# }
+```*/
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: std::io::Read,
+    T: DeserializeOwned,
+{
+    _from_deserializer(&mut Deserializer::from_reader(reader), Trailing::Reject)
+}
+
+impl<'de, T: Behaviour, R: Read<'de>> Deserializer<'de, T, R> {
+    fn eof(&self) -> Error {
+        Error::UnexpectedEof.at(self.position())
+    }
+    /// Error for the byte that was just consumed
+    fn unexpected(&self, got: u8, expected: Option<u8>) -> Error {
+        Error::SyntaxError(got, expected).at(self.position() - 1)
     }
-    fn peek_second(&self) -> Result<u8> {
-        let mut iter = self.input.iter();
-        iter.next().ok_or(Error::UnexpectedEof)?;
-        let val = iter.next().ok_or(Error::UnexpectedEof)?;
-        Ok(*val)
+    fn peek_next(&mut self) -> Result<u8> {
+        self.read.peek()?.ok_or_else(|| self.eof())
     }
     fn advance(&mut self) -> Result<u8> {
-        let ret = self.peek_next();
-        if self.input.is_empty() {
-            return Err(Error::UnexpectedEof);
+        self.read.next()?.ok_or_else(|| self.eof())
+    }
+    /// Goes one level deeper after consuming `l` or `d`
+    fn enter(&mut self) -> Result<()> {
+        if self.depth == self.max_depth {
+            return Err(Error::DepthLimitExceeded(self.max_depth).at(self.position() - 1));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+    /// Checks that a list or a dictionary with `len` elements can have one more
+    fn check_elements(&self, len: usize) -> Result<()> {
+        if len == self.limits.max_elements {
+            Err(Error::TooManyElements(len))
+        } else {
+            Ok(())
         }
-        self.input = &self.input[1..];
-        ret
     }
 
-    fn parse_byte_string(&mut self) -> Result<&'de [u8]> {
+    fn parse_byte_string(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+        let start = self.position();
+        match self.peek_next()? {
+            b'0'..=b'9' | b'+' | b'-' => {}
+            other => return Err(Error::SyntaxError(other, None).at(start)),
+        }
+        let strict = self.strict;
         let num_bytes = self.advance_to(b':')?;
-        let num_bytes: usize = btoi::btoi(num_bytes)?;
-        let bytes = self.advance_by(num_bytes)?;
-        Ok(bytes)
+        if strict {
+            check_canonical(&num_bytes).map_err(|e| e.at(start))?;
+        }
+        let num_bytes: usize = btoi::btoi(&num_bytes).map_err(|e| Error::from(e).at(start))?;
+        if num_bytes > self.limits.max_bytes_len {
+            return Err(Error::ByteStringTooLong(self.limits.max_bytes_len).at(start));
+        }
+        self.advance_by(num_bytes)
     }
 
-    fn advance_by(&mut self, len: usize) -> Result<&'de [u8]> {
-        if len > self.input.len() {
-            return Err(Error::UnexpectedEof);
-        }
-        let ret = &self.input[0..len];
-        self.input = &self.input[(ret.len())..];
-        Ok(ret)
+    fn parse_str(&mut self) -> Result<Reference<'de, '_, str>> {
+        let start = self.position();
+        let bytes = self.parse_byte_string()?;
+        to_str(bytes).map_err(|e| e.at(start))
+    }
+
+    fn advance_by(&mut self, len: usize) -> Result<Reference<'de, '_, [u8]>> {
+        self.read.read_bytes(len, &mut self.scratch)
     }
     #[inline(always)]
-    fn advance_to_e(&mut self) -> Result<&'de [u8]> {
+    fn advance_to_e(&mut self) -> Result<Reference<'de, '_, [u8]>> {
         self.advance_to(b'e')
     }
-    fn advance_to(&mut self, byte: u8) -> Result<&'de [u8]> {
-        let ret = slice_while(self.input, byte)?;
-        if ret.len() + 1 > self.input.len() {
-            return Err(Error::UnexpectedEof);
+    /// Parses `i<digits>e` and returns digits
+    fn parse_integer(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+        let start = self.position();
+        let marker = self.advance()?;
+        if marker != b'i' {
+            return Err(self.unexpected(marker, Some(b'i')));
         }
-        self.input = &self.input[(ret.len() + 1)..];
-        Ok(ret)
+        let strict = self.strict;
+        let digits = self.advance_to_e()?;
+        if strict {
+            check_canonical(&digits).map_err(|e| e.at(start))?;
+        }
+        Ok(digits)
+    }
+    fn parse_i64(&mut self) -> Result<i64> {
+        let start = self.position();
+        let digits = self.parse_integer()?;
+        btoi::btoi(&digits).map_err(|e| Error::from(e).at(start))
+    }
+    fn parse_u64(&mut self) -> Result<u64> {
+        let start = self.position();
+        let digits = self.parse_integer()?;
+        btoi::btoi(&digits).map_err(|e| Error::from(e).at(start))
+    }
+    fn advance_to(&mut self, byte: u8) -> Result<Reference<'de, '_, [u8]>> {
+        self.read.read_until(byte, &mut self.scratch)
     }
 }
-/// Takes everything while `!= 'e'`
-/// # Return
-/// Can return empty slice (`.len` == 0).
-fn slice_while(bytes: &[u8], end_byte: u8) -> Result<&[u8]> {
-    bytes
-        .iter()
-        .enumerate()
-        .find(|(_, x)| **x == end_byte)
-        .map(|(i, _)| &bytes[0..i])
-        .ok_or(Error::UnexpectedEof)
+/// Checks that `digits` has no `+` sign, leading zeros and is not `-0`
+fn check_canonical(digits: &[u8]) -> Result<()> {
+    match digits {
+        [b'+', ..] => Err(Error::PlusSign),
+        [b'-', b'0', ..] if digits.len() == 2 => Err(Error::NegativeZero),
+        [b'-', b'0', ..] | [b'0', _, ..] => Err(Error::LeadingZero),
+        _ => Ok(()),
+    }
 }
 
-impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de, T> {
+impl<'de, T: Behaviour, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<'de, T, R> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -223,16 +549,25 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
         V: Visitor<'de>,
     {
         match self.peek_next()? {
-            b'i' if self.peek_second()? == b'-' => self.deserialize_i64(visitor),
-            b'i' => self.deserialize_u64(visitor),
+            b'i' => {
+                let start = self.position();
+                let digits = self.parse_integer()?;
+                if digits.first() == Some(&b'-') {
+                    let int = btoi::btoi(&digits).map_err(|e| Error::from(e).at(start))?;
+                    visitor.visit_i64(int)
+                } else {
+                    let int = btoi::btoi(&digits).map_err(|e| Error::from(e).at(start))?;
+                    visitor.visit_u64(int)
+                }
+            }
             b'l' => self.deserialize_seq(visitor),
             b'd' => self.deserialize_map(visitor),
-            b'0'..=b'9' => T::deserialize_byte_string(self, visitor),
+            b'0'..=b'9' => T::visit_byte_string(self.parse_byte_string()?, visitor),
             // b'e' => {
             //     self.advance()?;
             //     // visitor.visit_unit()
             // }
-            other => Err(Error::SyntaxError(other, None)),
+            other => Err(Error::SyntaxError(other, None).at(self.position())),
         }
     }
     serde::forward_to_deserialize_any! {u8 u16 u32 i8 i16 i32 identifier ignored_any }
@@ -241,17 +576,16 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     where
         V: Visitor<'de>,
     {
+        let start = self.position();
         if self.advance()? == b'i' {
             let b = self.advance_to_e()?;
             if b.len() != 1 || ![b'0', b'1'].contains(&b[0]) {
-                Err(Error::Message(
-                    "expected integer between `0` to `1`".to_string(),
-                ))
+                Err(Error::Message("expected integer between `0` to `1`".to_string()).at(start))
             } else {
                 visitor.visit_bool(b[0] == b'1')
             }
         } else {
-            Err(Error::ExpectedInteger)
+            Err(Error::ExpectedInteger.at(start))
         }
     }
 
@@ -259,22 +593,14 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     where
         V: Visitor<'de>,
     {
-        let marker = self.advance()?;
-        if marker != b'i' {
-            return Err(Error::SyntaxError(marker, Some(b'i')));
-        }
-        visitor.visit_i64(btoi::btoi(self.advance_to_e()?)?)
+        visitor.visit_i64(self.parse_i64()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let marker = self.advance()?;
-        if marker != b'i' {
-            return Err(Error::SyntaxError(marker, Some(b'i')));
-        }
-        visitor.visit_u64(btoi::btoi(self.advance_to_e()?)?)
+        visitor.visit_u64(self.parse_u64()?)
     }
 
     fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
@@ -295,25 +621,16 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     where
         V: Visitor<'de>,
     {
-        let s = self.parse_byte_string()?;
-        if s.len() > 4 {
-            return Err(Error::ExpectedCharString);
-        }
-        let mut chars = std::str::from_utf8(s)?.chars();
-        if chars.clone().count() != 1 {
-            return Err(Error::ExpectedCharString);
-        }
-        let ch = chars.next().expect("this should not happen!!!");
-        visitor.visit_char(ch)
+        let start = self.position();
+        let s = self.parse_str()?;
+        visit_char(&s, visitor).map_err(|e| e.at(start))
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let bytes = self.parse_byte_string()?;
-        let s = std::str::from_utf8(bytes)?;
-        visitor.visit_borrowed_str(s)
+        visit_str(self.parse_str()?, visitor)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -327,7 +644,7 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(self.parse_byte_string()?)
+        visit_bytes(self.parse_byte_string()?, visitor)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
@@ -341,7 +658,7 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     where
         V: Visitor<'de>,
     {
-        if self.input.is_empty() {
+        if self.read.peek()?.is_none() {
             visitor.visit_none()
         } else {
             visitor.visit_some(self)
@@ -360,19 +677,28 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     where
         V: Visitor<'de>,
     {
+        let start = self.position();
         let decoded_name = self.parse_byte_string()?;
-        if name.as_bytes() != decoded_name {
-            Err(Error::ExpectedUnitStructName)
+        if name.as_bytes() != &*decoded_name {
+            Err(Error::ExpectedUnitStructName.at(start))
         } else {
             visitor.visit_unit()
         }
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == crate::raw::TOKEN {
+            self.read.begin_raw();
+            let ret = de::IgnoredAny::deserialize(&mut *self);
+            let raw = self.read.end_raw(&mut self.scratch);
+            ret?;
+            visit_bytes(raw, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
@@ -381,9 +707,18 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     {
         let peek = self.advance()?;
         if peek == b'l' {
-            visitor.visit_seq(ListAccess { de: self })
+            self.enter()?;
+            let mut access = ListAccess {
+                pos: self.position(),
+                de: &mut *self,
+                index: 0,
+            };
+            // errors of the visitor itself are about the last element read
+            let ret = visitor.visit_seq(&mut access).map_err(|e| e.at(access.pos));
+            self.leave();
+            ret
         } else {
-            Err(Error::SyntaxError(peek, Some(b'l')))
+            Err(self.unexpected(peek, Some(b'l')))
         }
     }
 
@@ -414,9 +749,20 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
     {
         let next = self.advance()?;
         if next == b'd' {
-            visitor.visit_map(self)
+            self.enter()?;
+            let mut access = DictAccess {
+                pos: self.position(),
+                de: &mut *self,
+                key: None,
+                len: 0,
+            };
+            // errors of the visitor itself, like a missing or duplicate
+            // field, are about the last key read or the end
+            let ret = visitor.visit_map(&mut access).map_err(|e| e.at(access.pos));
+            self.leave();
+            ret
         } else {
-            Err(Error::SyntaxError(next, Some(b'd')))
+            Err(self.unexpected(next, Some(b'd')))
         }
     }
 
@@ -444,57 +790,150 @@ impl<'de, 'a, T: Behaviour> de::Deserializer<'de> for &'a mut Deserializer<'de,
         match self.peek_next()? {
             b'd' => {
                 self.advance()?;
-                let res = visitor.visit_enum(&mut *self)?;
+                self.enter()?;
+                let res = visitor.visit_enum(&mut *self);
+                self.leave();
+                let res = res?;
                 if self.advance()? != b'e' {
-                    Err(Error::ExpectedEndOfDictionary)
+                    Err(Error::ExpectedEndOfDictionary.at(self.position() - 1))
                 } else {
                     Ok(res)
                 }
             }
             b'0'..=b'9' => {
                 use de::IntoDeserializer;
-                visitor
-                    .visit_enum(std::str::from_utf8(self.parse_byte_string()?)?.into_deserializer())
+                visitor.visit_enum((*self.parse_str()?).into_deserializer())
             }
-            _ => Err(Error::ExpectedDictionary),
+            _ => Err(Error::ExpectedDictionary.at(self.position())),
         }
     }
 }
 
-struct ListAccess<'m, 'de: 'm, T: Behaviour> {
-    de: &'m mut Deserializer<'de, T>,
+fn visit_char<'de, V: Visitor<'de>>(s: &str, visitor: V) -> Result<V::Value> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => visitor.visit_char(ch),
+        _ => Err(Error::ExpectedCharString),
+    }
 }
-impl<'de, 'm, Te: Behaviour> de::SeqAccess<'de> for ListAccess<'m, 'de, Te> {
+
+struct ListAccess<'m, 'de: 'm, T: Behaviour, R: Read<'de>> {
+    de: &'m mut Deserializer<'de, T, R>,
+    /// Index of the next element
+    index: usize,
+    /// Start of the last element or `e`
+    pos: usize,
+}
+impl<'de, Te: Behaviour, R: Read<'de>> de::SeqAccess<'de> for ListAccess<'_, 'de, Te, R> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: DeserializeSeed<'de>,
     {
+        let start = self.de.position();
+        self.pos = start;
         if self.de.peek_next()? == b'e' {
             // TODO: safe
             self.de.advance()?;
             Ok(None)
         } else {
-            Ok(Some(seed.deserialize(&mut *self.de)?))
+            self.de
+                .check_elements(self.index)
+                .map_err(|e| e.at(start))?;
+            let value = seed
+                .deserialize(&mut *self.de)
+                .map_err(|e| e.at(start).in_path(PathSegment::Index(self.index)))?;
+            self.index += 1;
+            Ok(Some(value))
         }
     }
 }
 
-impl<'de, T: Behaviour> de::MapAccess<'de> for Deserializer<'de, T> {
+/// Dictionary key that outlives the scratch buffer it was read to
+enum Key<'de> {
+    Borrowed(&'de [u8]),
+    Copied(Vec<u8>),
+}
+
+impl<'de> Key<'de> {
+    fn new(key: &Reference<'de, '_, [u8]>) -> Self {
+        match key {
+            Reference::Borrowed(b) => Key::Borrowed(b),
+            Reference::Copied(c) => Key::Copied(c.to_vec()),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Key::Borrowed(b) => b,
+            Key::Copied(c) => c,
+        }
+    }
+}
+
+struct DictAccess<'m, 'de: 'm, T: Behaviour, R: Read<'de>> {
+    de: &'m mut Deserializer<'de, T, R>,
+    /// Current key, `None` if it is not a byte string
+    key: Option<Key<'de>>,
+    /// Number of keys so far
+    len: usize,
+    /// Start of the last key or `e`
+    pos: usize,
+}
+
+/// Checks that `key` is greater than the previous one
+fn check_key_order(last_key: &[u8], key: &[u8]) -> Result<()> {
+    match last_key.cmp(key) {
+        std::cmp::Ordering::Less => Ok(()),
+        std::cmp::Ordering::Equal => Err(Error::DuplicateKey(key.to_vec())),
+        std::cmp::Ordering::Greater => Err(Error::UnsortedKey(key.to_vec())),
+    }
+}
+
+impl<'de, T: Behaviour, R: Read<'de>> de::MapAccess<'de> for DictAccess<'_, 'de, T, R> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: DeserializeSeed<'de>,
     {
-        if self.peek_next()? == b'e' {
-            // TODO: safe
-            self.advance()?;
-
-            Ok(None)
-        } else {
-            Ok(Some(seed.deserialize(&mut *self)?))
+        let start = self.de.position();
+        self.pos = start;
+        match self.de.peek_next()? {
+            b'e' => {
+                // TODO: safe
+                self.de.advance()?;
+
+                Ok(None)
+            }
+            _ if self.len == self.de.limits.max_elements => {
+                Err(Error::TooManyElements(self.len).at(start))
+            }
+            b'0'..=b'9' | b'+' | b'-' => {
+                self.len += 1;
+                let strict = self.de.strict;
+                let key = self.de.parse_byte_string()?;
+                let last_key = self.key.replace(Key::new(&key));
+                if let (true, Some(last_key)) = (strict, last_key) {
+                    check_key_order(last_key.as_bytes(), &key).map_err(|e| e.at(start))?;
+                }
+                let key = seed
+                    .deserialize(MapKey::<T> {
+                        key,
+                        strict,
+                        _marker: PhantomData,
+                    })
+                    .map_err(|e| e.at(start))?;
+                Ok(Some(key))
+            }
+            _ if self.de.strict => Err(Error::ExpectedString.at(start)),
+            _ => {
+                self.len += 1;
+                self.key = None;
+                let key = seed.deserialize(&mut *self.de).map_err(|e| e.at(start))?;
+                Ok(Some(key))
+            }
         }
     }
 
@@ -502,11 +941,187 @@ impl<'de, T: Behaviour> de::MapAccess<'de> for Deserializer<'de, T> {
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self)
+        let start = self.de.position();
+        seed.deserialize(&mut *self.de)
+            .map_err(|e| match &self.key {
+                Some(key) => e
+                    .at(start)
+                    .in_path(PathSegment::Key(key.as_bytes().to_vec())),
+                None => e.at(start),
+            })
     }
 }
 
-impl<'de, Te: Behaviour> de::VariantAccess<'de> for &mut Deserializer<'de, Te> {
+/// Deserializer for an already parsed dictionary key
+struct MapKey<'de, 's, T: Behaviour> {
+    key: Reference<'de, 's, [u8]>,
+    /// Integer keys must be canonical like integers
+    strict: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<'de, T: Behaviour> de::Deserializer<'de> for MapKey<'de, '_, T> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        T::visit_byte_string(self.key, visitor)
+    }
+    serde::forward_to_deserialize_any! {
+        bool f32 f64 unit seq tuple_struct map struct identifier ignored_any
+    }
+
+    /// Integer keys are byte strings of decimal digits
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.strict {
+            check_canonical(&self.key)?;
+        }
+        visitor.visit_i64(btoi::btoi(&self.key)?)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.strict {
+            check_canonical(&self.key)?;
+        }
+        visitor.visit_u64(btoi::btoi(&self.key)?)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i64(visitor)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_u64(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_char(&to_str(self.key)?, visitor)
+    }
+
+    /// Tuple keys, like `[u8; 20]`, are the bytes of the byte string
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut seq = de::value::SeqDeserializer::<_, Error>::new(self.key.iter().copied());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_str(to_str(self.key)?, visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_bytes(self.key, visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name.as_bytes() != &*self.key {
+            Err(Error::ExpectedUnitStructName)
+        } else {
+            visitor.visit_unit()
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        use de::IntoDeserializer;
+        visitor.visit_enum((*to_str(self.key)?).into_deserializer())
+    }
+}
+
+impl<'de, Te: Behaviour, R: Read<'de>> de::VariantAccess<'de> for &mut Deserializer<'de, Te, R> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -517,7 +1132,8 @@ impl<'de, Te: Behaviour> de::VariantAccess<'de> for &mut Deserializer<'de, Te> {
     where
         T: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self)
+        let start = self.position();
+        seed.deserialize(&mut *self).map_err(|e| e.at(start))
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
@@ -535,7 +1151,7 @@ impl<'de, Te: Behaviour> de::VariantAccess<'de> for &mut Deserializer<'de, Te> {
     }
 }
 
-impl<'de, T: Behaviour> de::EnumAccess<'de> for &mut Deserializer<'de, T> {
+impl<'de, T: Behaviour, R: Read<'de>> de::EnumAccess<'de> for &mut Deserializer<'de, T, R> {
     type Error = Error;
 
     type Variant = Self;
@@ -544,7 +1160,8 @@ impl<'de, T: Behaviour> de::EnumAccess<'de> for &mut Deserializer<'de, T> {
     where
         V: DeserializeSeed<'de>,
     {
-        let val = seed.deserialize(&mut *self)?;
+        let start = self.position();
+        let val = seed.deserialize(&mut *self).map_err(|e| e.at(start))?;
         Ok((val, self))
     }
 }
@@ -555,6 +1172,7 @@ mod tests {
     type Ret = std::result::Result<(), Box<dyn std::error::Error>>;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn primitives() -> Ret {
         assert_eq!(from_str::<i32>("i1e")?, 1);
         assert_eq!(from_str::<i32>("i-1e")?, -1);
@@ -607,6 +1225,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn integer_keys() -> Ret {
+        use std::collections::BTreeMap;
+        let map = from_str::<BTreeMap<u32, &str>>("d1:01:a2:101:b1:21:ce")?;
+        assert_eq!(map, BTreeMap::from([(0, "a"), (10, "b"), (2, "c")]));
+        let map = from_str::<BTreeMap<i8, u8>>("d2:-1i1e3:127i2ee")?;
+        assert_eq!(map, BTreeMap::from([(-1, 1), (127, 2)]));
+
+        let err = from_str::<BTreeMap<u8, u8>>("d3:256i1ee").unwrap_err();
+        assert_eq!(err.position(), Some(1));
+        assert!(from_str::<BTreeMap<u64, u8>>("d1:xi1ee").is_err());
+
+        // canonical like integers in strict mode
+        assert_eq!(
+            from_str::<BTreeMap<i8, u8>>("d2:01i1ee")?,
+            BTreeMap::from([(1, 1)])
+        );
+        let strict = |input: &[u8]| {
+            from_bytes_strict::<BTreeMap<i64, u8>>(input).map_err(|e| e.kind().clone())
+        };
+        assert_eq!(
+            strict(b"d2:-1i2e1:0i1ee")?,
+            BTreeMap::from([(0, 1), (-1, 2)])
+        );
+        assert_eq!(strict(b"d2:01i1ee"), Err(Error::LeadingZero));
+        assert_eq!(strict(b"d2:-0i1ee"), Err(Error::NegativeZero));
+        assert_eq!(strict(b"d2:+1i1ee"), Err(Error::PlusSign));
+        let err = from_bytes_strict::<BTreeMap<u64, u8>>(b"d2:01i1ee").unwrap_err();
+        assert_eq!(err.position(), Some(1));
+        Ok(())
+    }
+
     #[test]
     fn options() -> Ret {
         assert_eq!(from_str::<Option<i32>>("")?, None);
@@ -735,4 +1385,373 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn strict() -> Ret {
+        use std::collections::BTreeMap;
+
+        assert_eq!(from_bytes_strict::<i64>(b"i0e")?, 0);
+        assert_eq!(from_bytes_strict::<i64>(b"i-10e")?, -10);
+        assert_eq!(
+            from_bytes_strict::<i64>(b"i-0e").map_err(|e| e.kind().clone()),
+            Err(Error::NegativeZero)
+        );
+        assert_eq!(
+            from_bytes_strict::<i64>(b"i-01e").map_err(|e| e.kind().clone()),
+            Err(Error::LeadingZero)
+        );
+        assert_eq!(
+            from_bytes_strict::<u64>(b"i00e").map_err(|e| e.kind().clone()),
+            Err(Error::LeadingZero)
+        );
+        assert_eq!(
+            from_bytes_strict::<u64>(b"i+1e").map_err(|e| e.kind().clone()),
+            Err(Error::PlusSign)
+        );
+        assert_eq!(from_bytes::<u64>(b"i01e")?, 1);
+
+        assert_eq!(from_bytes_strict::<&str>(b"0:")?, "");
+        assert_eq!(
+            from_bytes_strict::<&str>(b"03:abc").map_err(|e| e.kind().clone()),
+            Err(Error::LeadingZero)
+        );
+        assert_eq!(
+            from_bytes_strict::<&str>(b"+3:abc").map_err(|e| e.kind().clone()),
+            Err(Error::PlusSign)
+        );
+        assert_eq!(from_bytes::<&str>(b"03:abc")?, "abc");
+
+        type Map<'a> = BTreeMap<&'a str, i64>;
+        assert_eq!(from_bytes_strict::<Map>(b"d1:ai1e2:aai2e1:bi3ee")?.len(), 3);
+        assert_eq!(
+            from_bytes_strict::<Map>(b"d1:bi1e1:ai2ee").map_err(|e| e.kind().clone()),
+            Err(Error::UnsortedKey(b"a".to_vec()))
+        );
+        assert_eq!(
+            from_bytes_strict::<Map>(b"d1:ai1e1:ai2ee").map_err(|e| e.kind().clone()),
+            Err(Error::DuplicateKey(b"a".to_vec()))
+        );
+        assert_eq!(
+            from_bytes_strict::<Vec<Map>>(b"ld1:ai1eed1:ai1eee")?.len(),
+            2
+        );
+        assert_eq!(from_bytes::<Map>(b"d1:bi1e1:ai2ee")?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn error_position() {
+        use std::collections::BTreeMap;
+
+        let position = |r: Result<BTreeMap<&str, Vec<u64>>>| r.unwrap_err().position();
+        assert_eq!(position(from_bytes(b"d1:ali1ei2ee")), Some(12));
+        assert_eq!(position(from_bytes(b"d1:ali1ei2ex")), Some(11));
+        assert_eq!(position(from_bytes(b"d1:ali1ei-2eee")), Some(8));
+        assert_eq!(position(from_bytes(b"d1:ali1e1:xee")), Some(8));
+        assert_eq!(position(from_bytes(b"d1:ali1ei2ix")), Some(12));
+        assert_eq!(position(from_bytes(b"d1:ali1eee1:x")), Some(10));
+        assert_eq!(position(from_bytes(b"di1eli1eee")), Some(1));
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct S {
+            a: u8,
+            b: char,
+        }
+        let err = from_bytes::<S>(b"d1:ai300e1:b1:ce").unwrap_err();
+        assert_eq!(err.position(), Some(4));
+        assert_eq!(
+            err.to_string(),
+            "a: invalid value: integer `300`, expected u8 at byte 4"
+        );
+        let err = from_bytes::<S>(b"d1:ai3e1:b2:cce").unwrap_err();
+        assert_eq!(err.kind(), &Error::ExpectedCharString);
+        assert_eq!(err.position(), Some(10));
+        // at the end of the dictionary
+        let err = from_bytes::<S>(b"d1:ai3ee").unwrap_err();
+        assert_eq!(err.to_string(), "missing field `b` at byte 7");
+        // at the second key
+        let err = from_bytes::<S>(b"d1:ai3e1:ai4e1:b1:ce").unwrap_err();
+        assert_eq!(err.to_string(), "duplicate field `a` at byte 7");
+        let err = from_bytes::<(u8, u8)>(b"li1ee").unwrap_err();
+        assert_eq!(err.position(), Some(4));
+        assert_eq!(
+            err.kind(),
+            &de::Error::invalid_length(1, &"a tuple of size 2")
+        );
+        let err = from_bytes::<S>(b"d1:ai3e1:b1:c").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected EOF at byte 13");
+        let err = from_bytes::<u8>(b"i1x").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected EOF at byte 3");
+        let err = from_bytes::<Vec<u8>>(b"i1e").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: expected `l`, got `i` at byte 0"
+        );
+    }
+
+    #[test]
+    fn error_path() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct File {
+            length: u64,
+        }
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Info {
+            files: Vec<File>,
+        }
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct MetaInfo {
+            info: Info,
+        }
+
+        let err =
+            from_bytes::<MetaInfo>(b"d4:infod5:filesld6:lengthi1eed6:length1:xeeee").unwrap_err();
+        assert_eq!(err.path().unwrap().to_string(), "info.files[1].length");
+        assert_eq!(err.position(), Some(38));
+        assert_eq!(
+            err.to_string(),
+            "info.files[1].length: syntax error: expected `i`, got `1` at byte 38"
+        );
+        let err = from_bytes::<MetaInfo>(b"d4:infod5:filesldeeee").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "info.files[0]: missing field `length` at byte 17"
+        );
+        let err = from_bytes::<MetaInfo>(b"d4:infod5:filesld6:lengthi1e").unwrap_err();
+        assert_eq!(err.kind(), &Error::UnexpectedEof);
+        assert_eq!(err.path().unwrap().to_string(), "info.files[0]");
+        let err = from_bytes::<MetaInfo>(b"d4:infod5:filesi1eee").unwrap_err();
+        assert_eq!(err.path().unwrap().to_string(), "info.files");
+        assert!(from_bytes::<MetaInfo>(b"de").unwrap_err().path().is_none());
+        assert_eq!(
+            from_bytes::<Vec<Vec<u8>>>(b"lleli1ei256eee")
+                .unwrap_err()
+                .path()
+                .unwrap()
+                .to_string(),
+            "[1][1]"
+        );
+    }
+
+    /// Returns at most one byte per `read` call
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(out)) => {
+                    *out = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn reader() -> Ret {
+        use crate::Value;
+        use serde_bytes::ByteBuf;
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct S {
+            name: String,
+            pieces: ByteBuf,
+            list: Vec<i64>,
+            map: BTreeMap<String, (u8, char)>,
+            unit: (),
+        }
+        let input: &[u8] = b"d4:listli-1ei2ee3:mapd1:ali1e1:xee4:name4:spam\
+                             6:pieces3:\x00\xFF\x014:unit0:e";
+        let expected = from_bytes::<S>(input)?;
+        assert_eq!(from_reader::<_, S>(Trickle(input))?, expected);
+        assert_eq!(from_reader::<_, S>(input)?, expected);
+        assert_eq!(
+            from_reader::<_, Value>(Trickle(input))?,
+            from_bytes::<Value>(input)?
+        );
+        assert_eq!(from_reader::<_, Option<u8>>(Trickle(b""))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn reader_errors() {
+        struct Broken;
+        impl std::io::Read for Broken {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("broken pipe"))
+            }
+        }
+        let err = from_reader::<_, u8>(Broken).unwrap_err();
+        assert_eq!(err.kind(), &Error::Io("broken pipe".to_string()));
+        let err = from_reader::<_, u8>(std::io::Read::chain(&b"i1"[..], Broken)).unwrap_err();
+        assert_eq!(err.to_string(), "broken pipe at byte 2");
+
+        // the length prefix is not trusted to preallocate
+        let err = from_reader::<_, serde_bytes::ByteBuf>(Trickle(b"99999999999:ab")).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected EOF at byte 14");
+        let err = from_reader::<_, u8>(Trickle(b"i1ei2e")).unwrap_err();
+        assert_eq!(err.to_string(), "syntax error: unexpected `i` at byte 3");
+        let err = from_reader::<_, std::collections::HashMap<String, Vec<u8>>>(Trickle(
+            b"d1:ali1ei256eee",
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a[1]: invalid value: integer `256`, expected u8 at byte 8"
+        );
+
+        let mut de = Deserializer::from_reader(Trickle(b"d1:bi1e1:ai2ee")).strict(true);
+        let err = std::collections::HashMap::<String, u8>::deserialize(&mut de).unwrap_err();
+        assert_eq!(err.kind(), &Error::UnsortedKey(b"a".to_vec()));
+        assert_eq!(err.position(), Some(7));
+    }
+
+    #[test]
+    fn depth_limit() -> Ret {
+        use crate::{Value, ValueRef};
+        use serde::de::IgnoredAny;
+
+        let deep = |open: &str, depth: usize| open.repeat(depth) + &"e".repeat(depth);
+        let input = deep("l", 100_000);
+        let err = from_str::<Value>(&input).unwrap_err();
+        assert_eq!(err.kind(), &Error::DepthLimitExceeded(DEFAULT_MAX_DEPTH));
+        assert_eq!(err.position(), Some(DEFAULT_MAX_DEPTH));
+        assert!(from_str::<ValueRef>(&input).is_err());
+        assert!(from_str::<IgnoredAny>(&input).is_err());
+        assert!(from_str_auto::<Value>(&input).is_err());
+        assert!(from_str::<Value>(&deep("d1:a", 100_000)).is_err());
+        assert!(from_reader::<_, Value>(input.as_bytes()).is_err());
+
+        from_str::<Value>(&deep("l", DEFAULT_MAX_DEPTH))?;
+        let input = deep("l", 200);
+        let mut de = Deserializer::from_str(&input).max_depth(200);
+        Value::deserialize(&mut de)?;
+        let mut de = Deserializer::from_str("li1ee").max_depth(0);
+        let err = Value::deserialize(&mut de).unwrap_err();
+        assert_eq!(err.to_string(), "nesting depth exceeds 0 at byte 0");
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        enum E {
+            N(Box<E>),
+            U,
+        }
+        let input = "d1:N".repeat(100_000) + "1:U" + &"e".repeat(100_000);
+        assert_eq!(
+            from_str::<E>(&input).unwrap_err().kind(),
+            &Error::DepthLimitExceeded(DEFAULT_MAX_DEPTH)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn limits() -> Ret {
+        use std::collections::BTreeMap;
+
+        const LIMITS: Limits = Limits {
+            max_bytes_len: 3,
+            max_elements: 2,
+            max_size: 16,
+        };
+        fn de(input: &[u8]) -> Deserializer<'_, Simple> {
+            Deserializer::from_bytes(input).limits(LIMITS)
+        }
+        let error = |input: &[u8]| {
+            let err = Vec::<serde_bytes::ByteBuf>::deserialize(&mut de(input)).unwrap_err();
+            (err.kind().clone(), err.position())
+        };
+        let map_error = |input: &[u8]| {
+            let err = BTreeMap::<&str, u8>::deserialize(&mut de(input)).unwrap_err();
+            (err.kind().clone(), err.position())
+        };
+
+        assert_eq!(
+            Vec::<&str>::deserialize(&mut de(b"l3:abc0:e"))?,
+            vec!["abc", ""]
+        );
+        assert_eq!(error(b"l4:abcde"), (Error::ByteStringTooLong(3), Some(1)));
+        assert_eq!(
+            error(b"l99999999999999:e"),
+            (Error::ByteStringTooLong(3), Some(1))
+        );
+        assert_eq!(error(b"l0:0:0:e"), (Error::TooManyElements(2), Some(5)));
+        assert_eq!(
+            map_error(b"d1:ai1e1:bi2e1:ci3ee"),
+            (Error::TooManyElements(2), Some(13))
+        );
+        assert_eq!(
+            BTreeMap::<&str, u8>::deserialize(&mut de(b"d1:ai1e1:bi2ee"))?.len(),
+            2
+        );
+        assert_eq!(error(b"l3:abc"), (Error::UnexpectedEof, Some(6)));
+        assert_eq!(
+            map_error(b"d3:abci100e3:abdi1ee"),
+            (Error::DocumentTooLarge(16), Some(16))
+        );
+
+        let limits = Limits {
+            max_size: 12,
+            ..Limits::default()
+        };
+        let input = b"l3:abc3:abce";
+        let mut de = Deserializer::from_bytes(input).limits(limits);
+        crate::Value::deserialize(&mut de)?;
+        for max_size in [0, 1, 5, 8, 11] {
+            let limits = Limits {
+                max_size,
+                ..Limits::default()
+            };
+            let mut de = Deserializer::from_bytes(input).limits(limits);
+            let err = crate::Value::deserialize(&mut de).unwrap_err();
+            assert_eq!(err.kind(), &Error::DocumentTooLarge(max_size));
+            assert_eq!(err.position(), Some(max_size));
+            let mut de = Deserializer::from_reader(Trickle(input)).limits(limits);
+            let err = crate::Value::deserialize(&mut de).unwrap_err();
+            assert_eq!(err.kind(), &Error::DocumentTooLarge(max_size));
+            assert_eq!(err.position(), Some(max_size));
+        }
+        let mut de = Deserializer::from_reader(Trickle(b"99999999999:abc")).limits(limits);
+        let err = serde_bytes::ByteBuf::deserialize(&mut de).unwrap_err();
+        assert_eq!(err.kind(), &Error::DocumentTooLarge(12));
+        Ok(())
+    }
+
+    #[test]
+    fn prefix() -> Ret {
+        assert_eq!(from_bytes_prefix::<u8>(b"i1e")?, (1, &b""[..]));
+        assert_eq!(from_bytes_prefix::<&str>(b"1:ai2e")?, ("a", &b"i2e"[..]));
+        assert_eq!(
+            from_bytes_prefix::<Vec<u8>>(b"li1eee\xFF")?,
+            (vec![1], &b"e\xFF"[..])
+        );
+        assert_eq!(
+            from_bytes_prefix::<Option<u8>>(b"i1e\x00")?,
+            (Some(1), &b"\x00"[..])
+        );
+        let err = from_bytes_prefix::<Vec<u8>>(b"li1e").unwrap_err();
+        assert_eq!(err.kind(), &Error::UnexpectedEof);
+
+        let mut de = Deserializer::from_bytes_auto(b"i1e1:x");
+        assert_eq!(de.remaining(), b"i1e1:x");
+        assert_eq!(u8::deserialize(&mut de)?, 1);
+        assert_eq!(de.remaining(), b"1:x");
+        assert!(de.end().is_err());
+        assert_eq!(<&str>::deserialize(&mut de)?, "x");
+        assert_eq!(de.remaining(), b"");
+        de.end()?;
+
+        let mut de = Deserializer::from_reader(Trickle(b"i1ee"));
+        assert_eq!(u8::deserialize(&mut de)?, 1);
+        assert_eq!(
+            de.end().unwrap_err().to_string(),
+            "syntax error: unexpected `e` at byte 3"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,343 @@
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, DeserializeOwned};
+
+use super::{Deserializer, Limits, Trailing, DEFAULT_MAX_DEPTH};
+use crate::error::{DeError as Error, DeResult as Result};
+
+/// Result of [`PushParser::feed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress<T> {
+    /// The value is not complete yet
+    NeedMoreData,
+    /// The value was received and deserialized
+    Done(T),
+}
+
+/// Parser for input that arrives in chunks of any size, like messages
+/// received from a non-blocking socket.
+///
+/// Bytes passed to [`feed`](PushParser::feed) are buffered until they make up a
+/// whole value. They are scanned once as they arrive, the state is kept
+/// across nested lists and dictionaries and partially received byte strings.
+/// The complete value is deserialized like with [`from_bytes`](crate::from_bytes),
+/// positions in errors are counted from its start. The parser can't recover
+/// after an error.
+/// # Examples
+/** ```
+# use serde_bencoded::{Progress, PushParser};
+# use std::collections::BTreeMap;
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+// BEP 10 extension handshake
+let mut parser = PushParser::<BTreeMap<String, BTreeMap<String, u8>>>::new();
+assert_eq!(parser.feed(b"d1:md11:ut_me")?, Progress::NeedMoreData);
+assert_eq!(parser.feed(b"tadata")?, Progress::NeedMoreData);
+let Progress::Done(message) = parser.feed(b"i1eeed1:")? else { panic!() };
+assert_eq!(message["m"]["ut_metadata"], 1);
+// the start of the next message is kept
+assert_eq!(parser.buffered(), b"d1:");
+# Ok(())
+# }
+```*/
+pub struct PushParser<T> {
+    buf: Vec<u8>,
+    scanner: Scanner,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Default for PushParser<T> {
+    fn default() -> Self {
+        PushParser::new()
+    }
+}
+
+impl<T: DeserializeOwned> PushParser<T> {
+    pub fn new() -> Self {
+        PushParser {
+            buf: Vec::new(),
+            scanner: Scanner::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// See [`Deserializer::strict`]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.scanner.strict = strict;
+        self
+    }
+
+    /// See [`Deserializer::max_depth`], it is checked as bytes arrive
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.scanner.max_depth = max_depth;
+        self
+    }
+
+    /// See [`Deserializer::limits`]. [`Limits::max_size`] and
+    /// [`Limits::max_bytes_len`] are checked as bytes arrive, so a value
+    /// can't make the buffer grow past them.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.scanner.limits = limits;
+        self
+    }
+
+    /// Returns received bytes that are not part of a returned value yet
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Adds `data` to the input and returns the value if it is complete.
+    ///
+    /// Bytes after the value are kept for the next one, call `feed(&[])`
+    /// to get it if more than one value can arrive at once.
+    pub fn feed(&mut self, data: &[u8]) -> Result<Progress<T>> {
+        self.buf.extend_from_slice(data);
+        let end = match self.scanner.scan(&self.buf)? {
+            Some(end) => end,
+            None => return Ok(Progress::NeedMoreData),
+        };
+        let value = self.scanner.deserialize(&self.buf[..end])?;
+        self.buf.drain(..end);
+        Ok(Progress::Done(value))
+    }
+}
+
+/// Where [`Scanner`] is inside of a value
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Before the start of a value or `e`
+    Value,
+    /// Between `i` and `e`
+    Integer,
+    /// Length of a byte string that started at `start`
+    Length { len: usize, start: usize },
+    /// Number of bytes of a byte string still to receive
+    Bytes(usize),
+}
+
+/// Finds where a value ends in a buffer that grows as input arrives,
+/// without scanning a byte twice
+#[derive(Debug, Clone)]
+pub(crate) struct Scanner {
+    /// Number of bytes of the buffer that were scanned
+    scanned: usize,
+    state: State,
+    /// Number of lists and dictionaries the scanner is inside of
+    depth: usize,
+    pub(crate) strict: bool,
+    pub(crate) max_depth: usize,
+    pub(crate) limits: Limits,
+}
+
+impl Scanner {
+    pub(crate) fn new() -> Self {
+        Scanner {
+            scanned: 0,
+            state: State::Value,
+            depth: 0,
+            strict: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Deserializes a value [`Scanner::scan`] found, the scanner starts
+    /// over at the next byte
+    pub(crate) fn deserialize<'a, T: Deserialize<'a>>(&mut self, value: &'a [u8]) -> Result<T> {
+        self.scanned = 0;
+        let mut de = Deserializer::from_bytes(value)
+            .strict(self.strict)
+            .max_depth(self.max_depth)
+            .limits(self.limits);
+        super::_from_deserializer(&mut de, Trailing::Reject)
+    }
+
+    /// Scans the new bytes of `buf`, returns where the value at its start
+    /// ends if it's complete
+    pub(crate) fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>> {
+        while self.scanned < buf.len() {
+            let pos = self.scanned;
+            if pos == self.limits.max_size {
+                let max_size = self.limits.max_size;
+                return Err(Error::DocumentTooLarge(max_size).at(max_size));
+            }
+            let byte = buf[pos];
+            self.scanned += 1;
+            match self.state {
+                State::Value => match byte {
+                    b'i' => self.state = State::Integer,
+                    b'l' | b'd' => {
+                        if self.depth == self.max_depth {
+                            return Err(Error::DepthLimitExceeded(self.max_depth).at(pos));
+                        }
+                        self.depth += 1;
+                    }
+                    b'e' if self.depth > 0 => {
+                        self.depth -= 1;
+                        if self.depth == 0 {
+                            return Ok(Some(self.scanned));
+                        }
+                    }
+                    b'0'..=b'9' => {
+                        self.state = State::Length {
+                            len: (byte - b'0') as usize,
+                            start: pos,
+                        }
+                    }
+                    other => return Err(Error::SyntaxError(other, None).at(pos)),
+                },
+                State::Integer => match byte {
+                    b'e' => {
+                        if let Some(end) = self.value_done() {
+                            return Ok(Some(end));
+                        }
+                    }
+                    b'0'..=b'9' | b'-' | b'+' => {}
+                    other => return Err(Error::SyntaxError(other, Some(b'e')).at(pos)),
+                },
+                State::Length { len, start } => match byte {
+                    b'0'..=b'9' => {
+                        let len = len
+                            .checked_mul(10)
+                            .and_then(|len| len.checked_add((byte - b'0') as usize))
+                            .filter(|len| *len <= self.limits.max_bytes_len)
+                            .ok_or_else(|| {
+                                Error::ByteStringTooLong(self.limits.max_bytes_len).at(start)
+                            })?;
+                        self.state = State::Length { len, start };
+                    }
+                    b':' if len > self.limits.max_bytes_len => {
+                        return Err(Error::ByteStringTooLong(self.limits.max_bytes_len).at(start));
+                    }
+                    b':' if self.limits.max_size - self.scanned < len => {
+                        let max_size = self.limits.max_size;
+                        return Err(Error::DocumentTooLarge(max_size).at(max_size));
+                    }
+                    b':' => {
+                        self.state = State::Bytes(len);
+                        if len == 0 {
+                            if let Some(end) = self.value_done() {
+                                return Ok(Some(end));
+                            }
+                        }
+                    }
+                    other => return Err(Error::SyntaxError(other, Some(b':')).at(pos)),
+                },
+                State::Bytes(left) => {
+                    // the byte was already taken above
+                    let take = (left - 1).min(buf.len() - self.scanned);
+                    self.scanned += take;
+                    if take == left - 1 {
+                        if let Some(end) = self.value_done() {
+                            return Ok(Some(end));
+                        }
+                    } else {
+                        self.state = State::Bytes(left - 1 - take);
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns where the value ends if the scalar that was just scanned is
+    /// the top level value
+    fn value_done(&mut self) -> Option<usize> {
+        self.state = State::Value;
+        if self.depth == 0 {
+            Some(self.scanned)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeError, Value};
+
+    /// Feeds `input` one byte at a time
+    fn trickle(input: &[u8]) -> Result<Vec<Value>> {
+        let mut parser = PushParser::new();
+        let mut values = Vec::new();
+        for byte in input {
+            if let Progress::Done(value) = parser.feed(std::slice::from_ref(byte))? {
+                values.push(value);
+            }
+        }
+        assert_eq!(parser.buffered(), b"");
+        Ok(values)
+    }
+
+    #[test]
+    fn chunks() -> Result<()> {
+        let input: &[u8] = b"d1:ald2:xyi-12eee3:keyi0ee0:i7e11:hello worldle";
+        let expected: Vec<Value> = crate::StreamDeserializer::new(Deserializer::from_bytes(input))
+            .collect::<Result<_>>()?;
+        assert_eq!(expected.len(), 5);
+        assert_eq!(trickle(input)?, expected);
+
+        let mut parser = PushParser::<Value>::new();
+        let mut values = Vec::new();
+        for chunk in input.chunks(7) {
+            let mut progress = parser.feed(chunk)?;
+            while let Progress::Done(value) = progress {
+                values.push(value);
+                progress = parser.feed(&[])?;
+            }
+        }
+        assert_eq!(values, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn typed() -> Result<()> {
+        let mut parser = PushParser::<(u8, String)>::new();
+        assert_eq!(parser.feed(b"li1e5:he")?, Progress::NeedMoreData);
+        assert_eq!(parser.feed(b"llo")?, Progress::NeedMoreData);
+        assert_eq!(parser.feed(b"e")?, Progress::Done((1, "hello".to_string())));
+        assert_eq!(parser.feed(b"")?, Progress::NeedMoreData);
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let err = |input: &[u8]| trickle(input).unwrap_err();
+        assert_eq!(err(b"e").kind(), &DeError::SyntaxError(b'e', None));
+        assert_eq!(
+            err(b"li1xe").kind(),
+            &DeError::SyntaxError(b'x', Some(b'e'))
+        );
+        assert_eq!(err(b"l3-abce").position(), Some(2));
+        assert_eq!(err(b"i--1e").position(), Some(0));
+        assert_eq!(err(b"d1:ae").kind(), &DeError::SyntaxError(b'e', None));
+        assert_eq!(
+            err(b"99999999999999999999999:").kind(),
+            &DeError::ByteStringTooLong(usize::MAX)
+        );
+
+        let mut parser = PushParser::<Value>::new().max_depth(2);
+        let err = parser.feed(b"lll").unwrap_err();
+        assert_eq!(err.kind(), &DeError::DepthLimitExceeded(2));
+        assert_eq!(err.position(), Some(2));
+
+        let limits = Limits {
+            max_size: 7,
+            max_bytes_len: 5,
+            ..Limits::default()
+        };
+        let mut parser = PushParser::<Value>::new().limits(limits);
+        let err = parser.feed(b"6:").unwrap_err();
+        assert_eq!(err.kind(), &DeError::ByteStringTooLong(5));
+        let mut parser = PushParser::<Value>::new().limits(limits);
+        let err = parser.feed(b"l5:").unwrap_err();
+        assert_eq!(err.kind(), &DeError::DocumentTooLarge(7));
+        let mut parser = PushParser::<Value>::new().limits(limits);
+        let err = parser.feed(b"li1ei2ei3e").unwrap_err();
+        assert_eq!(err.kind(), &DeError::DocumentTooLarge(7));
+
+        let mut parser = PushParser::<Value>::new().strict(true);
+        assert_eq!(parser.feed(b"i03").unwrap(), Progress::NeedMoreData);
+        assert_eq!(parser.feed(b"e").unwrap_err().kind(), &DeError::LeadingZero);
+    }
+}
@@ -0,0 +1,320 @@
+use std::ops::Deref;
+
+use super::private;
+use crate::error::{DeError as Error, DeResult as Result};
+
+/// Source of bytes for [`Deserializer`](crate::Deserializer).
+///
+/// This trait is sealed, implemented by [`SliceRead`] and [`IoRead`].
+pub trait Read<'de>: private::Sealed {
+    /// Consumes the next byte, `None` at the end of input
+    #[doc(hidden)]
+    fn next(&mut self) -> Result<Option<u8>>;
+
+    /// Returns the next byte without consuming it, `None` at the end of input
+    #[doc(hidden)]
+    fn peek(&mut self) -> Result<Option<u8>>;
+
+    /// Returns the number of bytes consumed so far
+    #[doc(hidden)]
+    fn position(&self) -> usize;
+
+    /// Fails reads past `max_size` bytes from the start with
+    /// [`DeError::DocumentTooLarge`](Error::DocumentTooLarge)
+    #[doc(hidden)]
+    fn set_max_size(&mut self, max_size: usize);
+
+    /// Starts recording consumed bytes
+    #[doc(hidden)]
+    fn begin_raw(&mut self);
+
+    /// Stops recording and returns bytes consumed since [`Read::begin_raw`]
+    #[doc(hidden)]
+    fn end_raw<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Reference<'de, 's, [u8]>;
+
+    /// Consumes everything up to and including `byte`, returns bytes before it
+    #[doc(hidden)]
+    fn read_until<'s>(
+        &'s mut self,
+        byte: u8,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>>;
+
+    /// Consumes exactly `len` bytes
+    #[doc(hidden)]
+    fn read_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>>;
+}
+
+/// Bytes either borrowed from the input (`'b`) or copied to the scratch
+/// buffer (`'c`).
+#[derive(Debug)]
+pub enum Reference<'b, 'c, T: ?Sized> {
+    Borrowed(&'b T),
+    Copied(&'c T),
+}
+
+impl<T: ?Sized> Deref for Reference<'_, '_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Reference::Borrowed(b) => b,
+            Reference::Copied(c) => c,
+        }
+    }
+}
+
+/// Reads from a byte slice, byte strings are borrowed from it
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+    index: usize,
+    /// Where [`Read::begin_raw`] was called
+    raw_start: usize,
+    /// `slice` is not read past this, it's less than `slice.len()` only
+    /// if the slice is larger than the maximum size
+    end: usize,
+}
+
+impl<'a> SliceRead<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        SliceRead {
+            slice,
+            index: 0,
+            raw_start: 0,
+            end: slice.len(),
+        }
+    }
+
+    /// Returns the bytes that are not consumed yet
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.slice[self.index..]
+    }
+
+    fn eof(&self) -> Error {
+        if self.end < self.slice.len() {
+            Error::DocumentTooLarge(self.end).at(self.end)
+        } else {
+            Error::UnexpectedEof.at(self.end)
+        }
+    }
+}
+
+impl private::Sealed for SliceRead<'_> {}
+impl<'a> Read<'a> for SliceRead<'a> {
+    #[inline]
+    fn next(&mut self) -> Result<Option<u8>> {
+        let ret = self.peek()?;
+        if ret.is_some() {
+            self.index += 1;
+        }
+        Ok(ret)
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Result<Option<u8>> {
+        match self.slice[..self.end].get(self.index) {
+            None if self.end < self.slice.len() => Err(self.eof()),
+            ret => Ok(ret.copied()),
+        }
+    }
+
+    #[inline]
+    fn position(&self) -> usize {
+        self.index
+    }
+
+    fn set_max_size(&mut self, max_size: usize) {
+        self.end = self.slice.len().min(max_size);
+    }
+
+    fn begin_raw(&mut self) {
+        self.raw_start = self.index;
+    }
+
+    fn end_raw<'s>(&'s mut self, _scratch: &'s mut Vec<u8>) -> Reference<'a, 's, [u8]> {
+        Reference::Borrowed(&self.slice[self.raw_start..self.index])
+    }
+
+    fn read_until<'s>(
+        &'s mut self,
+        byte: u8,
+        _scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'a, 's, [u8]>> {
+        let rest = &self.slice[self.index..self.end];
+        let ret = slice_while(rest, byte).ok_or_else(|| self.eof())?;
+        self.index += ret.len() + 1;
+        Ok(Reference::Borrowed(ret))
+    }
+
+    fn read_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        _scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'a, 's, [u8]>> {
+        let rest = &self.slice[self.index..self.end];
+        if len > rest.len() {
+            return Err(self.eof());
+        }
+        self.index += len;
+        Ok(Reference::Borrowed(&rest[..len]))
+    }
+}
+
+/// Takes everything while `!= end_byte`, `None` if there is no `end_byte`
+/// # Return
+/// Can return empty slice (`.len` == 0).
+fn slice_while(bytes: &[u8], end_byte: u8) -> Option<&[u8]> {
+    bytes
+        .iter()
+        .position(|x| *x == end_byte)
+        .map(|i| &bytes[0..i])
+}
+
+/// Reads from a [`std::io::Read`], byte strings are copied to a scratch
+/// buffer that is reused.
+///
+/// Reads one byte at a time outside of byte strings, wrap unbuffered
+/// sources like [`File`](std::fs::File) or
+/// [`TcpStream`](std::net::TcpStream) in a [`BufReader`](std::io::BufReader).
+pub struct IoRead<R> {
+    reader: R,
+    peeked: Option<u8>,
+    position: usize,
+    max_size: usize,
+    /// Consumed bytes since [`Read::begin_raw`]
+    raw: Option<Vec<u8>>,
+}
+
+impl<R: std::io::Read> IoRead<R> {
+    pub fn new(reader: R) -> Self {
+        IoRead {
+            reader,
+            peeked: None,
+            position: 0,
+            max_size: usize::MAX,
+            raw: None,
+        }
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn eof(&self) -> Error {
+        Error::UnexpectedEof.at(self.position)
+    }
+
+    fn too_large(&self) -> Error {
+        Error::DocumentTooLarge(self.max_size).at(self.max_size)
+    }
+
+    fn io_error(&self, e: std::io::Error) -> Error {
+        Error::from(e).at(self.position)
+    }
+}
+
+impl<R> private::Sealed for IoRead<R> {}
+impl<'de, R: std::io::Read> Read<'de> for IoRead<R> {
+    fn next(&mut self) -> Result<Option<u8>> {
+        let ret = self.peek()?;
+        if let Some(byte) = ret {
+            self.peeked = None;
+            self.position += 1;
+            if let Some(raw) = &mut self.raw {
+                raw.push(byte);
+            }
+        }
+        Ok(ret)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            let mut byte = 0;
+            loop {
+                match self.reader.read(std::slice::from_mut(&mut byte)) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => break,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(self.io_error(e)),
+                }
+            }
+            if self.position >= self.max_size {
+                return Err(self.too_large());
+            }
+            self.peeked = Some(byte);
+        }
+        Ok(self.peeked)
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    fn begin_raw(&mut self) {
+        self.raw = Some(Vec::new());
+    }
+
+    fn end_raw<'s>(&'s mut self, scratch: &'s mut Vec<u8>) -> Reference<'de, 's, [u8]> {
+        *scratch = self.raw.take().unwrap_or_default();
+        Reference::Copied(scratch)
+    }
+
+    fn read_until<'s>(
+        &'s mut self,
+        byte: u8,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        scratch.clear();
+        loop {
+            match self.next()? {
+                Some(b) if b == byte => return Ok(Reference::Copied(scratch)),
+                Some(b) => scratch.push(b),
+                None => return Err(self.eof()),
+            }
+        }
+    }
+
+    fn read_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        use std::io::Read as _;
+
+        scratch.clear();
+        if len == 0 {
+            return Ok(Reference::Copied(scratch));
+        }
+        if let Some(b) = self.peeked.take() {
+            scratch.push(b);
+            self.position += 1;
+        }
+        // grows `scratch` as data arrives instead of trusting `len`
+        let before = scratch.len();
+        let allowed = len.min(self.max_size.saturating_sub(self.position - before));
+        let read = (&mut self.reader)
+            .take((allowed - before) as u64)
+            .read_to_end(scratch);
+        self.position += scratch.len() - before;
+        read.map_err(|e| self.io_error(e))?;
+        if scratch.len() < allowed {
+            return Err(self.eof());
+        }
+        if allowed < len {
+            return Err(self.too_large());
+        }
+        if let Some(raw) = &mut self.raw {
+            raw.extend_from_slice(scratch);
+        }
+        Ok(Reference::Copied(scratch))
+    }
+}
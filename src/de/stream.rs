@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+
+use serde::Deserialize;
+
+use super::{read::Read, Behaviour, Deserializer, Simple, SliceRead};
+use crate::error::DeResult as Result;
+
+/// Iterator over values that follow each other in the input without
+/// separators, like messages on a stream.
+///
+/// Created with [`Deserializer::into_iter`]. Works with both `Simple` and
+/// `Auto` behaviours. Iteration stops after the first error.
+/// # Examples
+/** ```
+# use serde_bencoded::{Deserializer, Value};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let mut stream = Deserializer::from_bytes(b"i1e3:abcli2ee").into_iter::<Value>();
+assert_eq!(stream.next().transpose()?, Some(Value::Integer(1)));
+assert_eq!(stream.byte_offset(), 3);
+assert_eq!(stream.next().transpose()?, Some(Value::Bytes(b"abc".to_vec())));
+assert_eq!(stream.byte_offset(), 8);
+assert_eq!(stream.next().transpose()?, Some(Value::List(vec![Value::Integer(2)])));
+assert_eq!(stream.next().transpose()?, None);
+# Ok(())
+# }
+```*/
+pub struct StreamDeserializer<'de, T, B: Behaviour = Simple, R: Read<'de> = SliceRead<'de>> {
+    de: Deserializer<'de, B, R>,
+    failed: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<'de, T, B, R> StreamDeserializer<'de, T, B, R>
+where
+    T: Deserialize<'de>,
+    B: Behaviour,
+    R: Read<'de>,
+{
+    pub fn new(de: Deserializer<'de, B, R>) -> Self {
+        StreamDeserializer {
+            de,
+            failed: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the byte offset where the last value returned by
+    /// [`next`](Iterator::next) ends, it is where the next one starts.
+    pub fn byte_offset(&self) -> usize {
+        self.de.position()
+    }
+}
+
+impl<'de, T, B, R> Iterator for StreamDeserializer<'de, T, B, R>
+where
+    T: Deserialize<'de>,
+    B: Behaviour,
+    R: Read<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
+        let start = self.de.position();
+        let ret = match self.de.read.peek() {
+            Ok(None) => return None,
+            Ok(Some(_)) => T::deserialize(&mut self.de).map_err(|e| e.at(start)),
+            Err(e) => Err(e),
+        };
+        self.failed = ret.is_err();
+        Some(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_str, Deserializer, Value};
+    use serde::Deserialize;
+    type Ret = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn stream() -> Ret {
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Message {
+            Ping { ping: u8 },
+            Data(String),
+        }
+        let input = "d4:pingi1ee4:datad4:pingi2ee";
+        let mut stream = Deserializer::from_str(input).into_iter::<Value>();
+        for end in [11, 17, 28] {
+            assert!(stream.next().transpose()?.is_some());
+            assert_eq!(stream.byte_offset(), end);
+        }
+        assert!(stream.next().is_none());
+        assert_eq!(stream.byte_offset(), input.len());
+
+        let messages = Deserializer::from_str_auto(input)
+            .into_iter::<Message>()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            messages,
+            [
+                Message::Ping { ping: 1 },
+                Message::Data("data".to_string()),
+                Message::Ping { ping: 2 }
+            ]
+        );
+
+        let values = Deserializer::from_reader(input.as_bytes())
+            .into_iter::<Value>()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(values[2], from_str::<Value>("d4:pingi2ee")?);
+        assert_eq!(Deserializer::from_str("").into_iter::<u8>().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn stream_error() {
+        let mut stream = Deserializer::from_str("i1ei2xi3e").into_iter::<u8>();
+        assert_eq!(stream.next(), Some(Ok(1)));
+        let err = stream.next().unwrap().unwrap_err();
+        assert_eq!(err.position(), Some(3));
+        assert_eq!(stream.next(), None);
+    }
+}
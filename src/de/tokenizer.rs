@@ -0,0 +1,408 @@
+use serde::de::{Deserialize, IgnoredAny};
+
+use super::{check_key_order, read::Reference, Deserializer, Simple};
+use crate::error::{DeError as Error, DeResult as Result};
+
+/// Piece of a bencoded document returned by [`Tokenizer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'de> {
+    /// Digits of `i<digits>e`, in the range of [`i64`] if negative and of
+    /// [`u64`] otherwise, see [`Token::as_i64`] and [`Token::as_u64`]
+    Int(&'de [u8]),
+    /// `<len>:<bytes>`, dictionary keys are byte strings too
+    Bytes(&'de [u8]),
+    /// `l`
+    ListStart,
+    /// `d`
+    DictStart,
+    /// `e` that ends a list or a dictionary
+    End,
+}
+
+impl Token<'_> {
+    /// Returns the integer if it's [`Token::Int`] in the range of [`i64`]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Token::Int(digits) => btoi::btoi(digits).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer if it's [`Token::Int`] in the range of [`u64`]
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Token::Int(digits) => btoi::btoi(digits).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// List or dictionary the tokenizer is inside of
+struct Frame<'de> {
+    dict: bool,
+    /// Number of elements or keys so far
+    len: usize,
+    /// Last key of a dictionary
+    key: Option<&'de [u8]>,
+    /// Dictionary key was read, its value was not
+    in_value: bool,
+}
+
+/// Walks a document token by token without building values, for code that
+/// only needs to look at parts of it.
+///
+/// Every token comes with the byte offset where it starts. The input is
+/// checked the same way [`Deserializer`] checks it, including
+/// [strict mode](Deserializer::strict), [depth](Deserializer::max_depth) and
+/// [limits](Deserializer::limits), so tokens always form one valid value.
+/// After an error [`next_token`](Tokenizer::next_token) and
+/// [`skip_value`](Tokenizer::skip_value) return it again, iteration stops
+/// after yielding it once.
+/// # Examples
+/** ```
+# use serde_bencoded::{Token, Tokenizer};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let mut tokens = Tokenizer::new(b"d4:infod4:name1:xe3:urli1ee");
+assert_eq!(tokens.next().transpose()?, Some((0, Token::DictStart)));
+assert_eq!(tokens.next().transpose()?, Some((1, Token::Bytes(b"info"))));
+// the whole `info` dictionary in one call
+assert_eq!(tokens.skip_value()?, b"d4:name1:xe");
+assert_eq!(tokens.next().transpose()?, Some((18, Token::Bytes(b"url"))));
+assert_eq!(tokens.next().transpose()?, Some((23, Token::Int(b"1"))));
+assert_eq!(tokens.next().transpose()?, Some((26, Token::End)));
+assert_eq!(tokens.next().transpose()?, None);
+# Ok(())
+# }
+```*/
+pub struct Tokenizer<'de> {
+    de: Deserializer<'de, Simple>,
+    stack: Vec<Frame<'de>>,
+    /// The top level value was read
+    done: bool,
+    /// Error that stopped the tokenizer
+    error: Option<Error>,
+}
+
+impl<'de> Tokenizer<'de> {
+    pub fn new(input: &'de [u8]) -> Self {
+        Tokenizer::from_deserializer(Deserializer::from_bytes(input))
+    }
+
+    /// Uses the settings of `de`, like [`Deserializer::strict`]
+    pub fn from_deserializer(de: Deserializer<'de, Simple>) -> Self {
+        Tokenizer {
+            de,
+            stack: Vec::new(),
+            done: false,
+            error: None,
+        }
+    }
+
+    /// Returns the number of bytes consumed so far
+    pub fn byte_offset(&self) -> usize {
+        self.de.position()
+    }
+
+    /// Returns the next token and its offset, `None` after the end of the
+    /// top level value. Bytes after it are an error. Returns the error that
+    /// stopped the tokenizer again if there was one.
+    pub fn next_token(&mut self) -> Result<Option<(usize, Token<'de>)>> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        let ret = self.read_token();
+        if let Err(e) = &ret {
+            self.error = Some(e.clone());
+        }
+        ret
+    }
+
+    /// Skips the value the next token would start, with everything nested
+    /// in it, and returns its bytes. Where a dictionary key is expected the
+    /// key is skipped and its encoded bytes, like `1:a`, are returned, use
+    /// it after a key to skip the value. Returns the error that stopped the
+    /// tokenizer again if there was one.
+    pub fn skip_value(&mut self) -> Result<&'de [u8]> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        let ret = self.read_value();
+        if let Err(e) = &ret {
+            self.error = Some(e.clone());
+        }
+        ret
+    }
+
+    fn read_token(&mut self) -> Result<Option<(usize, Token<'de>)>> {
+        if self.done {
+            self.de.end()?;
+            return Ok(None);
+        }
+        let start = self.de.position();
+        let token = match self.de.peek_next()? {
+            b'e' if !self.in_value() && !self.stack.is_empty() => {
+                self.de.advance()?;
+                self.de.leave();
+                self.stack.pop();
+                self.value_done();
+                Token::End
+            }
+            b'i' if !self.expects_key() => {
+                self.check_elements()?;
+                let digits = match self.de.parse_integer()? {
+                    Reference::Borrowed(b) => b,
+                    Reference::Copied(_) => unreachable!("slices are borrowed"),
+                };
+                // the same range as `Deserializer::deserialize_any` accepts
+                if digits.first() == Some(&b'-') {
+                    btoi::btoi::<i64>(digits).map_err(|e| Error::from(e).at(start))?;
+                } else {
+                    btoi::btoi::<u64>(digits).map_err(|e| Error::from(e).at(start))?;
+                }
+                self.value_done();
+                Token::Int(digits)
+            }
+            b'l' | b'd' if !self.expects_key() => {
+                self.check_elements()?;
+                let dict = self.de.advance()? == b'd';
+                self.de.enter()?;
+                self.stack.push(Frame {
+                    dict,
+                    len: 0,
+                    key: None,
+                    in_value: false,
+                });
+                if dict {
+                    Token::DictStart
+                } else {
+                    Token::ListStart
+                }
+            }
+            b'0'..=b'9' | b'+' | b'-' => {
+                self.check_elements()?;
+                let bytes = self.parse_byte_string()?;
+                if self.expects_key() {
+                    self.key_done(bytes).map_err(|e| e.at(start))?;
+                } else {
+                    self.value_done();
+                }
+                Token::Bytes(bytes)
+            }
+            _ if self.expects_key() => return Err(Error::ExpectedString.at(start)),
+            other => return Err(Error::SyntaxError(other, None).at(start)),
+        };
+        Ok(Some((start, token)))
+    }
+
+    fn read_value(&mut self) -> Result<&'de [u8]> {
+        if self.done {
+            self.de.end()?;
+            return Err(Error::UnexpectedEof.at(self.de.position()));
+        }
+        if self.expects_key() {
+            let before = self.de.remaining();
+            return match self.read_token()? {
+                Some((_, Token::Bytes(_))) => {
+                    Ok(&before[..before.len() - self.de.remaining().len()])
+                }
+                // `e` of the dictionary
+                _ => Err(Error::ExpectedString.at(self.de.position() - 1)),
+            };
+        }
+        let start = self.de.position();
+        if self.de.peek_next()? == b'e' {
+            return Err(Error::SyntaxError(b'e', None).at(start));
+        }
+        self.check_elements()?;
+        let before = self.de.remaining();
+        IgnoredAny::deserialize(&mut self.de).map_err(|e| e.at(start))?;
+        let raw = &before[..before.len() - self.de.remaining().len()];
+        self.value_done();
+        Ok(raw)
+    }
+
+    fn parse_byte_string(&mut self) -> Result<&'de [u8]> {
+        match self.de.parse_byte_string()? {
+            Reference::Borrowed(b) => Ok(b),
+            Reference::Copied(_) => unreachable!("slices are borrowed"),
+        }
+    }
+
+    fn expects_key(&self) -> bool {
+        matches!(self.stack.last(), Some(f) if f.dict && !f.in_value)
+    }
+
+    fn in_value(&self) -> bool {
+        matches!(self.stack.last(), Some(f) if f.in_value)
+    }
+
+    /// Checks the element limit before an element or a key is read
+    fn check_elements(&self) -> Result<()> {
+        match self.stack.last() {
+            Some(frame) if !frame.in_value => self
+                .de
+                .check_elements(frame.len)
+                .map_err(|e| e.at(self.de.position())),
+            _ => Ok(()),
+        }
+    }
+
+    fn key_done(&mut self, key: &'de [u8]) -> Result<()> {
+        let strict = self.de.strict;
+        let frame = self.stack.last_mut().expect("inside of a dictionary");
+        if let (true, Some(last_key)) = (strict, frame.key) {
+            check_key_order(last_key, key)?;
+        }
+        frame.key = Some(key);
+        frame.in_value = true;
+        Ok(())
+    }
+
+    fn value_done(&mut self) {
+        match self.stack.last_mut() {
+            Some(frame) => {
+                frame.len += 1;
+                frame.in_value = false;
+            }
+            None => self.done = true,
+        }
+    }
+}
+
+impl<'de> Iterator for Tokenizer<'de> {
+    type Item = Result<(usize, Token<'de>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        self.next_token().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeError, Limits};
+
+    fn tokens(input: &[u8]) -> Result<Vec<Token<'_>>> {
+        Tokenizer::new(input).map(|t| t.map(|(_, t)| t)).collect()
+    }
+
+    #[test]
+    fn tokens_and_offsets() -> Result<()> {
+        use Token::*;
+        assert_eq!(
+            tokens(b"ld1:ai-1e1:bleei7e0:e")?,
+            [
+                ListStart,
+                DictStart,
+                Bytes(b"a"),
+                Int(b"-1"),
+                Bytes(b"b"),
+                ListStart,
+                End,
+                End,
+                Int(b"7"),
+                Bytes(b""),
+                End
+            ]
+        );
+        assert_eq!(tokens(b"i42e")?, [Int(b"42")]);
+        let offsets: Vec<usize> = Tokenizer::new(b"l3:abci1ee")
+            .map(|t| t.map(|(offset, _)| offset))
+            .collect::<Result<_>>()?;
+        assert_eq!(offsets, [0, 1, 6, 9]);
+        Ok(())
+    }
+
+    #[test]
+    fn integers() -> Result<()> {
+        let max = format!("i{}e", u64::MAX);
+        let token = tokens(max.as_bytes())?[0];
+        assert_eq!(token.as_u64(), Some(u64::MAX));
+        assert_eq!(token.as_i64(), None);
+        assert_eq!(token.as_u64(), crate::from_bytes(max.as_bytes()).ok());
+        let min = format!("i{}e", i64::MIN);
+        let token = tokens(min.as_bytes())?[0];
+        assert_eq!(token.as_i64(), Some(i64::MIN));
+        assert_eq!(token.as_u64(), None);
+        assert_eq!(Token::Bytes(b"1").as_u64(), None);
+        assert!(tokens(b"i18446744073709551616e").is_err());
+        assert!(tokens(b"i-9223372036854775809e").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn skip_value() -> Result<()> {
+        let mut tokens = Tokenizer::new(b"ld1:alee3:endi1ee");
+        assert_eq!(tokens.next_token()?, Some((0, Token::ListStart)));
+        assert_eq!(tokens.skip_value()?, b"d1:alee");
+        assert_eq!(tokens.skip_value()?, b"3:end");
+        assert_eq!(tokens.skip_value()?, b"i1e");
+        assert!(tokens.skip_value().is_err());
+
+        let mut tokens = Tokenizer::new(b"d1:ai1e1:bi2ee");
+        tokens.next_token()?;
+        assert_eq!(tokens.skip_value()?, b"1:a");
+        assert_eq!(tokens.skip_value()?, b"i1e");
+        assert_eq!(tokens.next_token()?, Some((7, Token::Bytes(b"b"))));
+        assert_eq!(tokens.skip_value()?, b"i2e");
+        assert_eq!(tokens.next_token()?, Some((13, Token::End)));
+        assert_eq!(tokens.next_token()?, None);
+
+        assert_eq!(Tokenizer::new(b"li1ee").skip_value()?, b"li1ee");
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let err = |input: &[u8]| tokens(input).unwrap_err();
+        assert_eq!(err(b"").kind(), &DeError::UnexpectedEof);
+        assert_eq!(err(b"e").kind(), &DeError::SyntaxError(b'e', None));
+        assert_eq!(err(b"i1ei2e").position(), Some(3));
+        assert_eq!(err(b"di1ei2ee").kind(), &DeError::ExpectedString);
+        assert_eq!(err(b"d1:ae").kind(), &DeError::SyntaxError(b'e', None));
+        assert_eq!(err(b"l4:abc").kind(), &DeError::UnexpectedEof);
+        assert_eq!(err(b"lixe").position(), Some(1));
+
+        let de = Deserializer::from_bytes(b"d1:bi1e1:ai2ee").strict(true);
+        let err = Tokenizer::from_deserializer(de)
+            .collect::<Result<Vec<_>>>()
+            .unwrap_err();
+        assert_eq!(err.kind(), &DeError::UnsortedKey(b"a".to_vec()));
+        assert_eq!(err.position(), Some(7));
+
+        let de = Deserializer::from_bytes(b"llleee").max_depth(2);
+        let err = Tokenizer::from_deserializer(de)
+            .collect::<Result<Vec<_>>>()
+            .unwrap_err();
+        assert_eq!(err.kind(), &DeError::DepthLimitExceeded(2));
+
+        let limits = Limits {
+            max_elements: 1,
+            ..Limits::default()
+        };
+        let de = Deserializer::from_bytes(b"li1ei2ee").limits(limits);
+        let mut tokens = Tokenizer::from_deserializer(de);
+        tokens.next_token().unwrap();
+        tokens.next_token().unwrap();
+        let err = tokens.skip_value().unwrap_err();
+        assert_eq!(err.kind(), &DeError::TooManyElements(1));
+        assert_eq!(tokens.next_token(), Err(err));
+        assert!(tokens.next().is_none());
+
+        // nothing is read after an error
+        let mut tokens = Tokenizer::new(b"lxei1e");
+        tokens.next_token().unwrap();
+        let err = tokens.next_token().unwrap_err();
+        assert_eq!(tokens.skip_value(), Err(err.clone()));
+        assert_eq!(tokens.next_token(), Err(err));
+        assert_eq!(tokens.byte_offset(), 1);
+
+        let mut tokens = Tokenizer::new(b"lx");
+        assert!(tokens.next().unwrap().is_ok());
+        assert!(tokens.next().unwrap().is_err());
+        assert!(tokens.next().is_none());
+    }
+}
@@ -10,14 +10,43 @@ pub enum SerError {
     Message(String),
     FloatingPointNotSupported,
     Io(String),
+    /// Dictionary key is not a byte string, an integer or a newtype of them
     DictionaryKeyMustBeString,
     /// Wrapper for [`FromUtf8Error`](std::string::FromUtf8Error)
     FromUtf8Error(std::string::FromUtf8Error),
     NoneNotSupported,
+    /// Dictionary key is not greater than the previous one, see
+    /// [`Encoder`](crate::Encoder)
+    UnsortedKey(Vec<u8>),
+    /// Dictionary has the same key twice, like a map and a
+    /// `#[serde(flatten)]` struct with the same field
+    DuplicateKey(Vec<u8>),
+    /// [`Encoder::key`](crate::Encoder::key) outside of a dictionary or
+    /// where a value is expected
+    UnexpectedKey,
+    /// Value in a dictionary without a key before it
+    ExpectedKey,
+    /// [`Encoder::end`](crate::Encoder::end) without an open list or
+    /// dictionary, or right after a key
+    UnexpectedEnd,
+    /// [`Encoder::finish`](crate::Encoder::finish) before every list and
+    /// dictionary is ended
+    Unfinished,
+    /// [`Encoder`](crate::Encoder) value after the top-level value is complete
+    TrailingValue,
+    /// [`ReaderBytes`](crate::ReaderBytes) reader yields a different number
+    /// of bytes than the declared length
+    ReaderBytesLength(usize),
 }
+/// Error of deserialization.
+///
+/// Errors of [`Deserializer`](crate::Deserializer) are wrapped in
+/// [`WithPosition`](DeError::WithPosition) and [`WithPath`](DeError::WithPath),
+/// match on [`kind`](DeError::kind) to find out what went wrong.
 #[derive(Debug, Clone, PartialEq)]
 pub enum DeError {
     Message(String),
+    Io(String),
     UnexpectedEof,
     /// (got byte, expected byte if some)
     SyntaxError(u8, Option<u8>),
@@ -32,6 +61,122 @@ pub enum DeError {
     ExpectedInteger,
     /// String with length at most 4
     ExpectedCharString,
+    /// Integer or byte string length starts with `0`, only in strict mode
+    LeadingZero,
+    /// Integer is `-0`, only in strict mode
+    NegativeZero,
+    /// Integer or byte string length starts with `+`, only in strict mode
+    PlusSign,
+    /// Dictionary key is not greater than the previous one, only in strict mode
+    UnsortedKey(Vec<u8>),
+    /// Dictionary key is the same as the previous one, only in strict mode
+    DuplicateKey(Vec<u8>),
+    /// Lists and dictionaries are nested deeper than the limit, see
+    /// [`Deserializer::max_depth`](crate::Deserializer::max_depth)
+    DepthLimitExceeded(usize),
+    /// Byte string is longer than the limit, see
+    /// [`Limits::max_bytes_len`](crate::Limits::max_bytes_len)
+    ByteStringTooLong(usize),
+    /// List or dictionary has more elements than the limit, see
+    /// [`Limits::max_elements`](crate::Limits::max_elements)
+    TooManyElements(usize),
+    /// Input is longer than the limit, see
+    /// [`Limits::max_size`](crate::Limits::max_size)
+    DocumentTooLarge(usize),
+    /// (byte offset in the input, error that occurred there)
+    WithPosition(usize, Box<DeError>),
+    /// (path to the value in the document, error that occurred there)
+    WithPath(Path, Box<DeError>),
+}
+
+/// Location of a value in a document, a chain of dictionary keys and
+/// list indices starting from the root. Displayed like `info.files[3].length`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+}
+
+/// One step of a [`Path`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Value of a dictionary entry with the key
+    Key(Vec<u8>),
+    /// Element of a list with the index
+    Index(usize),
+}
+
+impl Path {
+    /// Returns the segments, starting from the root
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => f.write_str(&String::from_utf8_lossy(key))?,
+                PathSegment::Key(key) => {
+                    f.write_fmt(format_args!(".{}", String::from_utf8_lossy(key)))?
+                }
+                PathSegment::Index(index) => f.write_fmt(format_args!("[{}]", index))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DeError {
+    /// Returns the byte offset in the input where the error occurred
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            DeError::WithPosition(position, _) => Some(*position),
+            DeError::WithPath(_, e) => e.position(),
+            _ => None,
+        }
+    }
+
+    /// Returns the path to the value that failed to deserialize
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            DeError::WithPath(path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without the location it occurred at
+    pub fn kind(&self) -> &DeError {
+        match self {
+            DeError::WithPosition(_, e) | DeError::WithPath(_, e) => e.kind(),
+            e => e,
+        }
+    }
+
+    /// Attaches `position` if the error doesn't have one yet
+    pub(crate) fn at(self, position: usize) -> Self {
+        match self {
+            DeError::WithPosition(..) => self,
+            DeError::WithPath(path, e) => DeError::WithPath(path, Box::new(e.at(position))),
+            e => DeError::WithPosition(position, Box::new(e)),
+        }
+    }
+
+    /// Prepends `segment` to the path
+    pub(crate) fn in_path(self, segment: PathSegment) -> Self {
+        match self {
+            DeError::WithPath(mut path, e) => {
+                path.segments.insert(0, segment);
+                DeError::WithPath(path, e)
+            }
+            e => DeError::WithPath(
+                Path {
+                    segments: vec![segment],
+                },
+                Box::new(e),
+            ),
+        }
+    }
 }
 
 impl From<btoi::ParseIntegerError> for DeError {
@@ -46,6 +191,12 @@ impl From<std::str::Utf8Error> for DeError {
     }
 }
 
+impl From<std::io::Error> for DeError {
+    fn from(ioe: std::io::Error) -> Self {
+        DeError::Io(ioe.to_string())
+    }
+}
+
 impl From<std::io::Error> for SerError {
     fn from(ioe: std::io::Error) -> Self {
         SerError::Io(ioe.to_string())
@@ -82,13 +233,30 @@ impl Display for SerError {
             SerError::Message(s) => f.write_str(s),
             SerError::Io(io) => f.write_str(io),
             SerError::DictionaryKeyMustBeString => {
-                f.write_str("only byte strings allowed to be keys in dictionary")
+                f.write_str("only byte strings and integers allowed to be keys in dictionary")
             }
             SerError::FloatingPointNotSupported => {
                 f.write_str("floating point numbers are not supported")
             }
             SerError::FromUtf8Error(ue) => f.write_fmt(format_args!("{}", ue)),
-            SerError::NoneNotSupported => f.write_str("`None` variant of `Option` is not supported, perhaps you need `#[serde(skip_serializing_if = \"Option::is_none\")]`")
+            SerError::NoneNotSupported => f.write_str("`None` variant of `Option` is not supported, perhaps you need `#[serde(skip_serializing_if = \"Option::is_none\")]`"),
+            SerError::UnsortedKey(key) => f.write_fmt(format_args!(
+                "dictionary key `{}` is not sorted",
+                String::from_utf8_lossy(key)
+            )),
+            SerError::DuplicateKey(key) => f.write_fmt(format_args!(
+                "duplicate dictionary key `{}`",
+                String::from_utf8_lossy(key)
+            )),
+            SerError::UnexpectedKey => f.write_str("dictionary key where a value is expected"),
+            SerError::ExpectedKey => f.write_str("expected dictionary key"),
+            SerError::UnexpectedEnd => f.write_str("no list or dictionary to end"),
+            SerError::Unfinished => f.write_str("list or dictionary is not ended"),
+            SerError::TrailingValue => f.write_str("value after the end of the top-level value"),
+            SerError::ReaderBytesLength(len) => f.write_fmt(format_args!(
+                "reader yields a different number of bytes than the declared {}",
+                len
+            )),
             }
     }
 }
@@ -97,10 +265,16 @@ impl Display for DeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DeError::Message(s) => f.write_str(s),
+            DeError::Io(io) => f.write_str(io),
             DeError::UnexpectedEof => f.write_str("unexpected EOF"),
-            DeError::SyntaxError(expected, got) => f.write_fmt(format_args!(
-                "syntax error: expected - {}, got - {:?}",
-                *expected as char, got
+            DeError::SyntaxError(got, Some(expected)) => f.write_fmt(format_args!(
+                "syntax error: expected `{}`, got `{}`",
+                expected.escape_ascii(),
+                got.escape_ascii()
+            )),
+            DeError::SyntaxError(got, None) => f.write_fmt(format_args!(
+                "syntax error: unexpected `{}`",
+                got.escape_ascii()
             )),
             DeError::ParseIntegerError(pie) => f.write_fmt(format_args!("{}", pie)),
             DeError::Utf8Error(ue) => f.write_fmt(format_args!("{}", ue)),
@@ -112,6 +286,33 @@ impl Display for DeError {
                 f.write_str("expected byte string with length at most 4 bytes")
             }
             DeError::ExpectedInteger => f.write_str("expected integer"),
+            DeError::LeadingZero => f.write_str("number has leading zero"),
+            DeError::NegativeZero => f.write_str("integer is negative zero"),
+            DeError::PlusSign => f.write_str("number has `+` sign"),
+            DeError::UnsortedKey(key) => f.write_fmt(format_args!(
+                "dictionary key `{}` is not sorted",
+                String::from_utf8_lossy(key)
+            )),
+            DeError::DuplicateKey(key) => f.write_fmt(format_args!(
+                "duplicate dictionary key `{}`",
+                String::from_utf8_lossy(key)
+            )),
+            DeError::DepthLimitExceeded(max) => {
+                f.write_fmt(format_args!("nesting depth exceeds {}", max))
+            }
+            DeError::ByteStringTooLong(max) => {
+                f.write_fmt(format_args!("byte string is longer than {} bytes", max))
+            }
+            DeError::TooManyElements(max) => {
+                f.write_fmt(format_args!("more than {} elements", max))
+            }
+            DeError::DocumentTooLarge(max) => {
+                f.write_fmt(format_args!("document is larger than {} bytes", max))
+            }
+            DeError::WithPosition(position, e) => {
+                f.write_fmt(format_args!("{} at byte {}", e, position))
+            }
+            DeError::WithPath(path, e) => f.write_fmt(format_args!("{}: {}", path, e)),
         }
     }
 }
@@ -0,0 +1,353 @@
+use std::fmt;
+
+use serde::{
+    de::{self, IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::{
+    error::{DeError, DeResult, SerError, SerResult},
+    from_bytes, RawValue, Serializer, SerializerOptions,
+};
+
+/// Info hash of a v1 torrent, SHA-1 of the `info` dictionary
+pub type InfoHashV1 = InfoHash<20>;
+/// Info hash of a v2 torrent, SHA-256 of the `info` dictionary
+pub type InfoHashV2 = InfoHash<32>;
+
+/// Hash of the bencoded `info` dictionary of a torrent.
+///
+/// [`Display`](fmt::Display) formats it as lowercase hex, like in
+/// `magnet:?xt=urn:btih:` links.
+/// # Examples
+/** ```
+# use serde_bencoded::info_hash_v1;
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let hash = info_hash_v1(b"d8:announce3:url4:infod6:lengthi1e4:name1:xee")?;
+assert_eq!(hash.to_string(), "607361303e836d09e97091ea3a6fc0ae34cb1ebd");
+assert_eq!(hash.to_base32(), "MBZWCMB6QNWQT2LQSHVDU36AVY2MWHV5");
+# Ok(())
+# }
+```*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InfoHash<const N: usize>([u8; N]);
+
+impl<const N: usize> InfoHash<N> {
+    pub fn new(bytes: [u8; N]) -> Self {
+        InfoHash(bytes)
+    }
+
+    /// Returns the hash bytes
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+
+    /// Formats as lowercase hex
+    pub fn to_hex(&self) -> String {
+        format!("{:x}", self)
+    }
+
+    /// Formats as uppercase base32 (RFC 4648), padded with `=`
+    pub fn to_base32(&self) -> String {
+        const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+        let mut ret = String::with_capacity(N.div_ceil(5) * 8);
+        for chunk in self.0.chunks(5) {
+            let mut block = [0; 5];
+            block[..chunk.len()].copy_from_slice(chunk);
+            let bits = block.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+            let chars = (chunk.len() * 8).div_ceil(5);
+            for i in 0..8 {
+                if i < chars {
+                    ret.push(ALPHABET[(bits >> (35 - i * 5)) as usize & 31] as char);
+                } else {
+                    ret.push('=');
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl InfoHashV1 {
+    /// Hashes the bencoded `info` with SHA-1.
+    ///
+    /// See [`InfoHashV2::from_info`] for how much of it is buffered.
+    pub fn from_info<T: Serialize>(info: &T) -> SerResult<Self> {
+        hash_serialize::<Sha1, _>(info).map(|hash| InfoHash(hash.into()))
+    }
+}
+
+impl InfoHashV2 {
+    /// Hashes the bencoded `info` with SHA-256.
+    ///
+    /// If keys of every dictionary come in sorted order, like in [`Value`](crate::Value)
+    /// or a struct with fields declared in sorted order, the output of
+    /// [`Serializer`](crate::Serializer) goes straight to the hasher.
+    /// Otherwise `info` is serialized a second time with
+    /// [`SerializerOptions::sort_keys`] set, which keeps the whole encoded
+    /// dictionary in memory to sort it. A [`ReaderBytes`](crate::ReaderBytes)
+    /// is consumed by the first attempt, so `info` with it must be sorted.
+    pub fn from_info<T: Serialize>(info: &T) -> SerResult<Self> {
+        hash_serialize::<Sha256, _>(info).map(|hash| InfoHash(hash.into()))
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for InfoHash<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        InfoHash(bytes)
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for InfoHash<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> fmt::LowerHex for InfoHash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl<const N: usize> fmt::UpperHex for InfoHash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02X}", b))
+    }
+}
+
+impl<const N: usize> fmt::Display for InfoHash<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+/// SHA-1 of the `info` dictionary of a bencoded torrent.
+///
+/// The exact bytes of `info` are hashed, the dictionary is not decoded or
+/// re-encoded.
+pub fn info_hash_v1(torrent: &[u8]) -> DeResult<InfoHashV1> {
+    let info = find_info(torrent)?;
+    Ok(InfoHash(Sha1::digest(info).into()))
+}
+
+/// SHA-256 of the `info` dictionary of a bencoded torrent.
+///
+/// The exact bytes of `info` are hashed, the dictionary is not decoded or
+/// re-encoded.
+pub fn info_hash_v2(torrent: &[u8]) -> DeResult<InfoHashV2> {
+    let info = find_info(torrent)?;
+    Ok(InfoHash(Sha256::digest(info).into()))
+}
+
+fn find_info(torrent: &[u8]) -> DeResult<&[u8]> {
+    let info = from_bytes::<InfoSpan>(torrent)?.0.as_bytes();
+    if info.first() != Some(&b'd') {
+        let position = info.as_ptr() as usize - torrent.as_ptr() as usize;
+        return Err(DeError::ExpectedDictionary.at(position));
+    }
+    Ok(info)
+}
+
+/// Hashes the canonical encoding of `value` whatever the default options are
+fn hash_serialize<D: Digest, T: Serialize>(value: &T) -> SerResult<sha1::digest::Output<D>> {
+    let mut hasher = HashWriter(D::new());
+    let options = SerializerOptions { sort_keys: false };
+    let mut serializer = Serializer::with_options(&mut hasher, options).require_sorted();
+    match value.serialize(&mut serializer) {
+        Ok(()) => return Ok(hasher.0.finalize()),
+        Err(SerError::UnsortedKey(_)) => {}
+        Err(e) => return Err(e),
+    }
+    // bytes hashed so far are not in canonical order, start over sorting
+    // dictionaries in memory
+    let mut hasher = HashWriter(D::new());
+    let options = SerializerOptions { sort_keys: true };
+    value.serialize(&mut Serializer::with_options(&mut hasher, options))?;
+    Ok(hasher.0.finalize())
+}
+
+/// Feeds written bytes to a hasher
+struct HashWriter<D>(D);
+
+impl<D: Digest> std::io::Write for HashWriter<D> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Value of the `info` key of a top level dictionary, other keys are skipped
+struct InfoSpan<'a>(RawValue<'a>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for InfoSpan<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct InfoVisitor;
+        impl<'de> Visitor<'de> for InfoVisitor {
+            type Value = InfoSpan<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a dictionary with `info` key")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut info = None;
+                while let Some(key) = map.next_key::<IsInfo>()? {
+                    if !key.0 {
+                        map.next_value::<IgnoredAny>()?;
+                    } else if info.is_some() {
+                        return Err(de::Error::duplicate_field("info"));
+                    } else {
+                        info = Some(map.next_value()?);
+                    }
+                }
+                info.map(InfoSpan)
+                    .ok_or_else(|| de::Error::missing_field("info"))
+            }
+        }
+        deserializer.deserialize_map(InfoVisitor)
+    }
+}
+
+/// Key that is compared to `info` without allocating
+struct IsInfo(bool);
+
+impl<'de> Deserialize<'de> for IsInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+        impl Visitor<'_> for KeyVisitor {
+            type Value = IsInfo;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a byte string key")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(IsInfo(v == b"info"))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.visit_bytes(v.as_bytes())
+            }
+        }
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bencode, ReaderBytes};
+    type Ret = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod6:lengthi1e4:name1:xe7:comment0:e";
+    const V1: &str = "607361303e836d09e97091ea3a6fc0ae34cb1ebd";
+    const V2: &str = "10792069979ff01361b5b8794864d56ec5a721b35ae00557277eac1c9fa4ef80";
+
+    #[test]
+    fn from_document() -> Ret {
+        assert_eq!(info_hash_v1(TORRENT)?.to_hex(), V1);
+        assert_eq!(info_hash_v2(TORRENT)?.to_string(), V2);
+        assert_eq!(
+            format!("{:X}", info_hash_v1(TORRENT)?),
+            V1.to_ascii_uppercase()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn from_serialize() -> Ret {
+        let info = bencode! { "name" => "x", "length" => 1 };
+        assert_eq!(InfoHashV1::from_info(&info)?, info_hash_v1(TORRENT)?);
+        assert_eq!(InfoHashV2::from_info(&info)?, info_hash_v2(TORRENT)?);
+
+        // fields are sorted even without `sort_dictionary` feature
+        #[derive(Serialize)]
+        struct Info {
+            name: &'static str,
+            length: u64,
+        }
+        let info = Info {
+            name: "x",
+            length: 1,
+        };
+        assert_eq!(InfoHashV1::from_info(&info)?.to_hex(), V1);
+        assert_eq!(InfoHashV2::from_info(&info)?.to_hex(), V2);
+
+        // sorted fields are streamed, the reader is read once
+        #[derive(Serialize)]
+        struct Sorted {
+            length: u64,
+            name: ReaderBytes<&'static [u8]>,
+        }
+        let info = Sorted {
+            length: 1,
+            name: ReaderBytes::new(1, b"x"),
+        };
+        assert_eq!(InfoHashV1::from_info(&info)?.to_hex(), V1);
+        #[derive(Serialize)]
+        struct Duplicate {
+            a: u8,
+            #[serde(flatten)]
+            rest: std::collections::BTreeMap<&'static str, u8>,
+        }
+        let info = Duplicate {
+            a: 1,
+            rest: [("a", 2)].into(),
+        };
+        assert_eq!(
+            InfoHashV1::from_info(&info),
+            Err(SerError::DuplicateKey(b"a".to_vec()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn base32() -> Ret {
+        assert_eq!(
+            info_hash_v1(TORRENT)?.to_base32(),
+            "MBZWCMB6QNWQT2LQSHVDU36AVY2MWHV5"
+        );
+        assert_eq!(
+            info_hash_v2(TORRENT)?.to_base32(),
+            "CB4SA2MXT7YBGYNVXB4UQZGVN3C2OINTLLQAKVZHP2WBZH5E56AA===="
+        );
+        assert_eq!(InfoHash::new(*b"f").to_base32(), "MY======");
+        assert_eq!(InfoHash::new(*b"foob").to_base32(), "MZXW6YQ=");
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        assert!(info_hash_v1(b"d8:announce3:urle").is_err());
+        assert!(info_hash_v1(b"d4:infode4:infodee").is_err());
+        assert!(info_hash_v1(b"d4:infode").is_err());
+        assert!(info_hash_v1(b"le").is_err());
+        assert!(info_hash_v2(b"d4:infodeei1e").is_err());
+        let err = info_hash_v1(b"d4:infoi1ee").unwrap_err();
+        assert_eq!(err.kind(), &DeError::ExpectedDictionary);
+        assert_eq!(err.position(), Some(7));
+        assert!(info_hash_v2(b"d4:info4:infoe").is_err());
+    }
+}
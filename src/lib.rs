@@ -1,8 +1,10 @@
 /*!
     Yet another encoding/decoding library for bencode.
 
-    I believe [`Deserializer`] doesn't allocate memory. [`Serializer`] allocates
-    only when `sort_dictionary` feature enabled. I think this crate is fast enough.
+    I believe [`Deserializer`] doesn't allocate memory when reading from a slice,
+    [`from_reader`] decodes from [`std::io::Read`] without buffering the whole input. [`Serializer`] allocates
+    buffers for dictionaries, reused between values: whole dictionaries when
+    sorting them and their keys to find duplicates otherwise, see [`SerializerOptions`]. I think this crate is fast enough.
 
     ## Examples
     For `.torrent` parsing example see [`examples directory`](https://github.com/knightpp/serde_bencoded/tree/master/examples)
@@ -22,9 +24,9 @@
     [`serde_with::skip_serializing_none`](https://docs.rs/serde_with/1.6.1/serde_with/attr.skip_serializing_none.html)
 
     ## Behaviour
-    This crate does sort dictionary by keys if `sort_dictionary` feature is
-    enabled (enabled by default). Otherwise order of elements can vary
-    (depends on [`Serialize`](serde::Serialize) trait implementation).
+    This crate does sort dictionary by keys if [`SerializerOptions::sort_keys`]
+    is set (by default if `sort_dictionary` feature is enabled). Otherwise order
+    of elements can vary (depends on [`Serialize`](serde::Serialize) trait implementation).
 
     ### Mapping of rust types to bencode
     - [`bool`] is [`integer`](#integers) either `i1e` or `i0e`.
@@ -41,11 +43,25 @@
           the `enum` and value is the variant.
     - `tuple`s and `array`s is [`lists`](#lists), tuples can be heterogeneous.
     - `struct Rgb(u8, u8, u8)` (tuple struct) is [`list`](#lists) of tuple values.
-    - [`HashMap`](std::collections::HashMap) is [`dictionary`](#dictionaries).
+    - [`HashMap`](std::collections::HashMap) is [`dictionary`](#dictionaries). Keys can be
+      strings, byte strings, chars, unit variants, integers (as byte strings of decimal
+      digits, `{1 => 2}` is `d1:1i2ee`), `[u8; N]` (as byte strings of the bytes)
+      and newtypes around them.
     - `struct`s is [`dictionary`](#dictionaries). Keys are field names, values are
       field values.
+    - `enum E { V { a: u8 } }` (struct variant) is `d1:Vd1:ai0eee`, [`dictionary`](#dictionaries)
+      with the variant as the key and its fields sorted like a `struct`'s.
     - [`f32`], [`f64`] is not supported.
 
+    ### Untyped documents
+    When the shape of a document is not known ahead of time deserialize
+    it to [`Value`]. It can be inspected with accessors like [`Value::get`]
+    and serialized back to bencode. [`ValueRef`] does the same without
+    copying byte strings out of the input. [`to_value`] and [`from_value`]
+    convert between [`Value`] and rust's types without going through bytes.
+    [`bencode!`] builds a [`Value`] from a literal. [`RawValue`] keeps the exact
+    bytes of a value, for example to hash the `info` dictionary of a torrent.
+
     ## Alternatives
     Arbitrary order. Search more on [`crates.io`](https://crates.io/search?q=bencode) or
     [`lib.rs`](https://lib.rs/search?q=bencode).
@@ -96,19 +112,60 @@
     Keys must be sorted as __raw__ strings. [`string`](#byte-strings)'s should be
     compared using a __binary comparison__.
 
+    [`Deserializer`] accepts malformed integers and unsorted keys unless
+    [strict mode](Deserializer::strict) is enabled, see [`from_bytes_strict`].
+
     Examples:
     - `de` == `{}`
     - `d4:rustl2:is7:awesomeee` == `{"rust" => ["is", "awesome"]}`
 
     ## Crate features
     ### sort_dictionary
-    Enables sort by keys when serializing to bencode dictionary.
+    Enables sort by keys when serializing to bencode dictionary, it's the
+    default of [`SerializerOptions::sort_keys`].
+    ### infohash
+    Adds [`info_hash_v1`] and [`info_hash_v2`] to compute SHA-1 and SHA-256
+    hashes of the `info` dictionary of a torrent, and [`InfoHash::from_info`](InfoHashV1::from_info)
+    to hash a serializable `info`, streaming it to the hasher if its keys are
+    already sorted.
+    ### tokio-codec
+    Adds [`BencodeCodec`], a [`tokio_util::codec`] decoder and encoder of
+    whole bencoded values.
+    ### tokio
+    Adds [`from_async_reader`], [`from_async_reader_with`] and [`to_async_writer`] for tokio's
+    [`AsyncRead`](tokio::io::AsyncRead) and [`AsyncWrite`](tokio::io::AsyncWrite).
 */
 
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "tokio-codec")]
+mod codec;
 mod de;
 mod error;
+#[cfg(feature = "infohash")]
+mod infohash;
+mod macros;
+mod raw;
 mod ser;
-
-pub use de::{from_bytes, from_bytes_auto, from_str, from_str_auto, Deserializer};
-pub use error::{DeError, DeResult, SerError, SerResult};
-pub use ser::{to_string, to_vec, to_writer, Serializer};
+mod value;
+
+#[cfg(feature = "tokio")]
+pub use async_io::{from_async_reader, from_async_reader_with, to_async_writer};
+#[cfg(feature = "tokio-codec")]
+pub use codec::BencodeCodec;
+pub use de::{
+    from_bytes, from_bytes_auto, from_bytes_prefix, from_bytes_strict, from_reader, from_str,
+    from_str_auto,
+    read::{IoRead, Read, Reference, SliceRead},
+    Deserializer, Limits, Progress, PushParser, StreamDeserializer, Token, Tokenizer,
+    DEFAULT_MAX_DEPTH,
+};
+pub use error::{DeError, DeResult, Path, PathSegment, SerError, SerResult};
+#[cfg(feature = "infohash")]
+pub use infohash::{info_hash_v1, info_hash_v2, InfoHash, InfoHashV1, InfoHashV2};
+pub use raw::{RawValue, RawValueBuf};
+pub use ser::{to_string, to_vec, to_writer, Encoder, ReaderBytes, Serializer, SerializerOptions};
+pub use value::{from_value, to_value, Value, ValueRef};
+
+#[doc(hidden)]
+pub use macros::__private;
@@ -0,0 +1,181 @@
+/// Builds a [`Value`](crate::Value) from a literal.
+///
+/// `key => value` pairs build a dictionary, `[...]` builds a list and `{...}`
+/// nests a dictionary. Dictionary keys can be anything that implements
+/// `AsRef<[u8]>` and are sorted as raw bytes. Any other value is converted
+/// with [`to_value`](crate::to_value), except byte string literals
+/// (`b"..."`) which become byte strings instead of lists of integers.
+///
+/// # Panics
+/// If an interpolated value can't be converted with
+/// [`to_value`](crate::to_value).
+/// # Examples
+/** ```
+# use serde_bencoded::{bencode, to_vec};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let length = 5;
+let value = bencode! {
+    "info" => { "name" => "x", "length" => length },
+    "announce" => b"http://127.0.0.1",
+    "list" => [1, "two", { "three" => 3 }],
+};
+assert_eq!(
+    to_vec(&value)?,
+    b"d8:announce16:http://127.0.0.14:infod6:lengthi5e4:name1:xe4:listli1e3:twod5:threei3eeee"
+);
+# Ok(())
+# }
+```*/
+#[macro_export]
+macro_rules! bencode {
+    // Munches `key => value` pairs of a dictionary.
+    (@dict $dict:ident) => {};
+    (@dict $dict:ident $key:expr => { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $dict.insert($crate::__private::key(&$key), $crate::bencode!({ $($value)* }));
+        $crate::bencode!(@dict $dict $($($rest)*)?);
+    };
+    (@dict $dict:ident $key:expr => [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $dict.insert($crate::__private::key(&$key), $crate::bencode!([ $($value)* ]));
+        $crate::bencode!(@dict $dict $($($rest)*)?);
+    };
+    (@dict $dict:ident $key:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $dict.insert($crate::__private::key(&$key), $crate::bencode!($value));
+        $crate::bencode!(@dict $dict $($($rest)*)?);
+    };
+
+    // Munches elements of a list.
+    (@list $list:ident) => {};
+    (@list $list:ident { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $list.push($crate::bencode!({ $($value)* }));
+        $crate::bencode!(@list $list $($($rest)*)?);
+    };
+    (@list $list:ident [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $list.push($crate::bencode!([ $($value)* ]));
+        $crate::bencode!(@list $list $($($rest)*)?);
+    };
+    (@list $list:ident $value:expr $(, $($rest:tt)*)?) => {
+        $list.push($crate::bencode!($value));
+        $crate::bencode!(@list $list $($($rest)*)?);
+    };
+
+    () => {
+        $crate::Value::Dict(::std::collections::BTreeMap::new())
+    };
+    ({ $($tt:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut dict = ::std::collections::BTreeMap::new();
+        $crate::bencode!(@dict dict $($tt)*);
+        $crate::Value::Dict(dict)
+    }};
+    ([ $($tt:tt)* ]) => {{
+        #[allow(unused_mut)]
+        let mut list = ::std::vec::Vec::new();
+        $crate::bencode!(@list list $($tt)*);
+        $crate::Value::List(list)
+    }};
+    ($key:expr => $($tt:tt)*) => {
+        $crate::bencode!({ $key => $($tt)* })
+    };
+    ($other:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{AnySerialize as _, ByteLiteral as _};
+        (&$crate::__private::Wrap(&$other)).bencode_value()
+    }};
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use crate::{to_value, Value};
+    use serde::Serialize;
+
+    pub fn key<K: AsRef<[u8]> + ?Sized>(key: &K) -> Vec<u8> {
+        key.as_ref().to_vec()
+    }
+
+    /// Picks [`ByteLiteral`] over [`AnySerialize`] by autoref-based
+    /// specialization, because `&[u8; N]` serializes as a list.
+    pub struct Wrap<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ByteLiteral {
+        fn bencode_value(&self) -> Value;
+    }
+
+    impl<const N: usize> ByteLiteral for Wrap<'_, &[u8; N]> {
+        fn bencode_value(&self) -> Value {
+            Value::Bytes(self.0.to_vec())
+        }
+    }
+
+    impl ByteLiteral for Wrap<'_, &[u8]> {
+        fn bencode_value(&self) -> Value {
+            Value::Bytes(self.0.to_vec())
+        }
+    }
+
+    pub trait AnySerialize {
+        fn bencode_value(&self) -> Value;
+    }
+
+    impl<T: Serialize + ?Sized> AnySerialize for &Wrap<'_, T> {
+        fn bencode_value(&self) -> Value {
+            match to_value(self.0) {
+                Ok(v) => v,
+                Err(e) => panic!("`bencode!` failed to convert value: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, to_vec, Value};
+    type Ret = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn dictionary() -> Ret {
+        let value = bencode! { "c" => 3, "a" => 1, "b" => 2 };
+        assert_eq!(value, from_bytes::<Value>(b"d1:ai1e1:bi2e1:ci3ee")?);
+        assert_eq!(to_vec(&value)?, b"d1:ai1e1:bi2e1:ci3ee");
+        assert_eq!(bencode!(), bencode!({}));
+        assert_eq!(to_vec(&bencode!({}))?, b"de");
+        Ok(())
+    }
+
+    #[test]
+    fn nested() -> Ret {
+        let files = vec!["a", "b"];
+        let value = bencode!({
+            "info" => {
+                "name" => "x",
+                "length" => 5,
+                "files" => files,
+                "pieces" => b"\x00\xFF",
+            },
+            b"announce" => [[], ["tracker"], { "k" => -1 }],
+            String::from("z") => bencode!([1, 2]),
+        });
+        assert_eq!(
+            to_vec(&value)?,
+            b"d8:announcellel7:trackered1:ki-1eee4:infod5:filesl1:a1:be\
+              6:lengthi5e4:name1:x6:pieces2:\x00\xFFe1:zli1ei2eee"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scalars() -> Ret {
+        assert_eq!(bencode!(-7), Value::Integer(-7));
+        assert_eq!(bencode!("str"), Value::Bytes(b"str".to_vec()));
+        assert_eq!(bencode!(b"bytes"), Value::Bytes(b"bytes".to_vec()));
+        assert_eq!(bencode!([]), Value::List(Vec::new()));
+        let bytes: &[u8] = b"slice";
+        assert_eq!(bencode!(bytes), Value::Bytes(b"slice".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsupported_value() {
+        bencode!({ "float" => 1.0 });
+    }
+}
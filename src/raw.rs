@@ -0,0 +1,219 @@
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{error::DeResult, from_bytes};
+
+/// Name of the newtype struct [`Deserializer`](crate::Deserializer) and
+/// [`Serializer`](crate::Serializer) handle specially for raw values
+pub(crate) const TOKEN: &str = "$serde_bencoded::private::RawValue";
+
+/// Bencoded value kept as the exact bytes it was parsed from.
+///
+/// Deserializing it takes the span of the input the value occupies without
+/// parsing it further, serializing it writes the bytes as is. Use it to get
+/// the original bytes of a part of a document, like the `info` dictionary
+/// of a torrent to compute the info hash. Only works with this crate's
+/// deserializer and borrows from the input, see [`RawValueBuf`] for the
+/// owned version.
+/// # Examples
+/** ```
+# use serde_bencoded::{from_bytes, to_vec, RawValue};
+# use serde::{Deserialize, Serialize};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+#[derive(Deserialize, Serialize)]
+struct MetaInfo<'a> {
+    announce: &'a str,
+    #[serde(borrow)]
+    info: RawValue<'a>,
+}
+// keys are not sorted, re-encoding would change them
+let input = b"d8:announce3:url4:infod4:name1:x6:lengthi1eee";
+let torrent: MetaInfo = from_bytes(input)?;
+assert_eq!(torrent.info.as_bytes(), b"d4:name1:x6:lengthi1ee");
+assert_eq!(to_vec(&torrent)?, input);
+# Ok(())
+# }
+```*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawValue<'de>(&'de [u8]);
+
+impl<'de> RawValue<'de> {
+    /// Checks that `bytes` is exactly one bencoded value
+    pub fn from_bytes(bytes: &'de [u8]) -> DeResult<Self> {
+        from_bytes::<serde::de::IgnoredAny>(bytes)?;
+        Ok(RawValue(bytes))
+    }
+
+    /// Returns the bencoded bytes
+    pub fn as_bytes(&self) -> &'de [u8] {
+        self.0
+    }
+
+    /// Copies the bytes to [`RawValueBuf`]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_owned(&self) -> RawValueBuf {
+        RawValueBuf(self.0.to_vec())
+    }
+}
+
+/// Owned [`RawValue`].
+///
+/// Deserializing it copies the span of the input, so it works with
+/// [`from_reader`](crate::from_reader) too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawValueBuf(Vec<u8>);
+
+impl RawValueBuf {
+    /// Checks that `bytes` is exactly one bencoded value
+    pub fn from_vec(bytes: Vec<u8>) -> DeResult<RawValueBuf> {
+        RawValue::from_bytes(&bytes)?;
+        Ok(RawValueBuf(bytes))
+    }
+
+    /// Returns the bencoded bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Borrows as [`RawValue`]
+    pub fn as_raw_value(&self) -> RawValue<'_> {
+        RawValue(&self.0)
+    }
+
+    /// Returns the bencoded bytes
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// Makes [`Serializer`](crate::Serializer) write the bytes as they are
+struct RawBytes<'a>(&'a [u8]);
+
+impl Serialize for RawBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &RawBytes(self.0))
+    }
+}
+
+impl Serialize for RawValueBuf {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.as_raw_value().serialize(serializer)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValue<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawVisitor;
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = RawValue<'de>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a raw value borrowed from the input")
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(RawValue(v))
+            }
+        }
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawValueBuf {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawVisitor;
+        impl Visitor<'_> for RawVisitor {
+            type Value = RawValueBuf;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a raw value")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(RawValueBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(RawValueBuf(v))
+            }
+        }
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_reader, from_value, to_value, to_vec, Value};
+    use std::collections::{BTreeMap, HashMap};
+    type Ret = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn raw_value() -> Ret {
+        let input: &[u8] = b"d1:bl1:xi-1ee1:ai01ee";
+        let raw: RawValue = from_bytes(input)?;
+        assert_eq!(raw.as_bytes(), input);
+
+        let map: HashMap<&str, RawValue> = from_bytes(input)?;
+        assert_eq!(map["a"].as_bytes(), b"i01e");
+        assert_eq!(map["b"].as_bytes(), b"l1:xi-1ee");
+        assert_eq!(map["b"].as_bytes().as_ptr(), input[4..].as_ptr());
+        assert_eq!(to_vec(&map["a"])?, b"i01e");
+        // values are written as they are
+        let map: BTreeMap<_, _> = map.into_iter().collect();
+        assert_eq!(to_vec(&map)?, b"d1:ai01e1:bl1:xi-1eee");
+
+        let map: BTreeMap<String, RawValueBuf> = from_reader(input)?;
+        assert_eq!(map["a"].as_bytes(), b"i01e");
+        assert_eq!(map["b"].as_bytes(), b"l1:xi-1ee");
+        assert_eq!(to_vec(&map)?, b"d1:ai01e1:bl1:xi-1eee");
+
+        let raw: RawValueBuf = from_bytes(b"li1ei2ee")?;
+        assert_eq!(to_value(&raw)?, from_bytes::<Value>(b"li1ei2ee")?);
+        let value = from_bytes::<Value>(b"d1:ai1ee")?;
+        assert_eq!(from_value::<RawValueBuf>(value)?.into_vec(), b"d1:ai1ee");
+        Ok(())
+    }
+
+    #[test]
+    fn raw_value_errors() {
+        assert!(from_bytes::<RawValue>(b"li1e").is_err());
+        assert!(from_bytes::<RawValue>(b"i1ei2e").is_err());
+        assert!(from_reader::<_, RawValueBuf>(&b"li1e"[..]).is_err());
+        assert!(RawValue::from_bytes(b"i1").is_err());
+        assert!(RawValueBuf::from_vec(b"1:ab".to_vec()).is_err());
+        assert_eq!(
+            RawValueBuf::from_vec(b"1:a".to_vec()).unwrap().as_bytes(),
+            b"1:a"
+        );
+    }
+}
@@ -2,27 +2,172 @@ use crate::error::{SerError as Error, SerResult as Result};
 use serde::{ser, Serialize};
 use std::io::Write;
 
+mod encoder;
 mod only_string_ser;
+mod reader_bytes;
 
+pub use encoder::Encoder;
+pub(crate) use only_string_ser::ByteSerializer;
+pub use reader_bytes::ReaderBytes;
+pub(crate) use reader_bytes::TOKEN as READER_BYTES_TOKEN;
+
+/// Writes bencode of [`Serialize`] types to a [`Write`].
+///
+/// [`to_writer`] and [`to_vec`] use it with the default
+/// [`SerializerOptions`].
+/// # Examples
+/** ```
+# use serde_bencoded::{Serializer, SerializerOptions};
+# use serde::Serialize;
+# use std::collections::HashMap;
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let map = HashMap::from([("b", 2), ("a", 1)]);
+
+let options = SerializerOptions { sort_keys: true };
+let mut serializer = Serializer::with_options(Vec::new(), options);
+map.serialize(&mut serializer)?;
+assert_eq!(serializer.into_inner(), b"d1:ai1e1:bi2ee");
+
+// keys are written in the order of the map
+let options = SerializerOptions { sort_keys: false };
+let mut serializer = Serializer::with_options(Vec::new(), options);
+map.serialize(&mut serializer)?;
+assert_eq!(serializer.into_inner().len(), 14);
+# Ok(())
+# }
+```*/
 pub struct Serializer<T: Write> {
     writer: T,
+    options: SerializerOptions,
     int_buf: itoa::Buffer,
+    /// Next byte string is a [`RawValue`](crate::RawValue) to write as is
+    raw: bool,
+    /// Next sequence is chunks of [`ReaderBytes`]
+    reader_bytes: bool,
+    /// Declared length and bytes left of [`ReaderBytes`] being written
+    chunks: Option<(usize, usize)>,
+    /// Dictionaries being sorted are written here
+    scratch: Scratch,
+    /// Keys of dictionaries that are not sorted must come in sorted order
+    require_sorted: bool,
+}
+
+/// Options of [`Serializer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializerOptions {
+    /// Sort dictionaries by keys as raw bytes, as bencode requires.
+    /// Otherwise keys are written in the order the type serializes them and
+    /// values aren't buffered, only the keys of open dictionaries are kept
+    /// in a reused buffer to find duplicates. Defaults to `true` if
+    /// `sort_dictionary` feature is enabled.
+    pub sort_keys: bool,
+}
+
+// derivable only without `sort_dictionary` feature
+#[allow(clippy::derivable_impls)]
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        SerializerOptions {
+            sort_keys: cfg!(feature = "sort_dictionary"),
+        }
+    }
 }
 
 impl<T: Write> Serializer<T> {
-    fn new(writer: T) -> Self {
+    /// Creates a serializer with the default [`SerializerOptions`]
+    pub fn new(writer: T) -> Self {
+        Serializer::with_options(writer, SerializerOptions::default())
+    }
+
+    /// Creates a serializer that writes to `writer` with `options`
+    pub fn with_options(writer: T, options: SerializerOptions) -> Self {
         Serializer {
             writer,
+            options,
             int_buf: itoa::Buffer::new(),
+            raw: false,
+            reader_bytes: false,
+            chunks: None,
+            scratch: Scratch::default(),
+            require_sorted: false,
         }
     }
+
+    /// Fails with [`Error::UnsortedKey`] if keys of a dictionary that is
+    /// not sorted come out of order, instead of writing them as is
+    #[cfg_attr(not(feature = "infohash"), allow(dead_code))]
+    pub(crate) fn require_sorted(mut self) -> Self {
+        self.require_sorted = true;
+        self
+    }
+
+    /// Returns the writer
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
 }
 
 impl<W: Write> Serializer<W> {
     fn write_byte(&mut self, byte: u8) -> Result<()> {
-        self.writer.write_all(&[byte])?;
+        self.write_all(&[byte])
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        write_to(&mut self.writer, &mut self.scratch, buf)
+    }
+
+    /// Writes `i<v>e`
+    fn write_integer<I: itoa::Integer>(&mut self, v: I) -> Result<()> {
+        self.write_byte(b'i')?;
+        let str = self.int_buf.format(v);
+        write_to(&mut self.writer, &mut self.scratch, str.as_bytes())?;
+        self.write_byte(b'e')?;
         Ok(())
     }
+
+    /// Writes `<len>:<v>`
+    fn write_byte_string(&mut self, v: &[u8]) -> Result<()> {
+        self.write_byte_string_len(v.len())?;
+        self.write_all(v)
+    }
+
+    /// Writes `<len>:`
+    fn write_byte_string_len(&mut self, len: usize) -> Result<()> {
+        let str = self.int_buf.format(len);
+        write_to(&mut self.writer, &mut self.scratch, str.as_bytes())?;
+        self.write_all(b":")
+    }
+
+    /// Writes a chunk of [`ReaderBytes`], checks it fits the declared length
+    fn write_chunk(&mut self, v: &[u8]) -> Result<()> {
+        if let Some((len, left)) = &mut self.chunks {
+            if v.len() > *left {
+                return Err(Error::ReaderBytesLength(*len));
+            }
+            *left -= v.len();
+        }
+        self.write_all(v)
+    }
+
+    /// Ends a list or [`ReaderBytes`]
+    fn end_seq(&mut self) -> Result<()> {
+        match self.chunks.take() {
+            Some((_, 0)) => Ok(()),
+            Some((len, _)) => Err(Error::ReaderBytesLength(len)),
+            None => self.write_byte(b'e'),
+        }
+    }
+}
+
+/// Writes to the scratch arena while a dictionary is sorted, to `writer`
+/// otherwise
+fn write_to<W: Write>(writer: &mut W, scratch: &mut Scratch, buf: &[u8]) -> Result<()> {
+    if scratch.depth > 0 {
+        scratch.bytes.extend_from_slice(buf);
+    } else {
+        writer.write_all(buf)?;
+    }
+    Ok(())
 }
 
 /// Serializes rust's type to bencode string
@@ -60,20 +205,14 @@ assert_eq!(&buf, b"4:abcd");
 # }
 ```*/
 pub fn to_writer<T: Serialize, W: Write>(value: &T, writer: W) -> Result<()> {
-    let mut serializer = Serializer {
-        writer,
-        int_buf: itoa::Buffer::new(),
-    };
+    let mut serializer = Serializer::new(writer);
     value.serialize(&mut serializer)?;
     Ok(())
 }
 /// Convenient function to get encoded value as bytes
 pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
-    let mut serializer = Serializer {
-        writer: &mut buf,
-        int_buf: itoa::Buffer::new(),
-    };
+    let mut serializer = Serializer::new(&mut buf);
     value.serialize(&mut serializer)?;
     Ok(buf)
 }
@@ -91,16 +230,11 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
 
     type SerializeTupleVariant = Self;
 
-    #[cfg(feature = "sort_dictionary")]
     type SerializeMap = StructMapSerializer<'s, W>;
-    #[cfg(not(feature = "sort_dictionary"))]
-    type SerializeMap = Self;
-    #[cfg(feature = "sort_dictionary")]
+
     type SerializeStruct = StructMapSerializer<'s, W>;
-    #[cfg(not(feature = "sort_dictionary"))]
-    type SerializeStruct = Self;
 
-    type SerializeStructVariant = Self;
+    type SerializeStructVariant = StructMapSerializer<'s, W>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         self.serialize_u64(v as u64)
@@ -119,11 +253,7 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.write_byte(b'i')?;
-        let str = self.int_buf.format(v);
-        self.writer.write_all(str.as_bytes())?;
-        self.write_byte(b'e')?;
-        Ok(())
+        self.write_integer(v)
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
@@ -139,11 +269,7 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.write_byte(b'i')?;
-        let str = self.int_buf.format(v);
-        self.writer.write_all(str.as_bytes())?;
-        self.write_byte(b'e')?;
-        Ok(())
+        self.write_integer(v)
     }
 
     fn serialize_f32(self, _v: f32) -> Result<Self::Ok> {
@@ -166,11 +292,14 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
 
     /// Serializes bytes as `Byte String`
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
-        let str = self.int_buf.format(v.len());
-        self.writer.write_all(str.as_bytes())?;
-        self.writer.write_all(b":")?;
-        self.writer.write_all(v)?;
-        Ok(())
+        if self.raw {
+            self.raw = false;
+            return self.write_all(v);
+        }
+        if self.chunks.is_some() {
+            return self.write_chunk(v);
+        }
+        self.write_byte_string(v)
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
@@ -185,8 +314,7 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
-        self.writer.write_all(b"0:")?;
-        Ok(())
+        self.write_all(b"0:")
     }
 
     fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok> {
@@ -202,11 +330,23 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
         variant.serialize(&mut *self)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: Serialize + ?Sized,
     {
-        value.serialize(&mut *self)
+        self.raw = name == crate::raw::TOKEN;
+        if name != READER_BYTES_TOKEN {
+            return value.serialize(&mut *self);
+        }
+        self.reader_bytes = true;
+        let ret = value.serialize(&mut *self);
+        self.reader_bytes = false;
+        // an error before the end of the chunks, other than the ones of the
+        // serializer, is an error of the reader
+        match (ret, self.chunks.take()) {
+            (Err(Error::Message(e)), Some(_)) => Err(Error::Io(e)),
+            (ret, _) => ret,
+        }
     }
 
     fn serialize_newtype_variant<T>(
@@ -226,7 +366,13 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
         Ok(())
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        if std::mem::take(&mut self.reader_bytes) {
+            let len = len.unwrap_or(0);
+            self.write_byte_string_len(len)?;
+            self.chunks = Some((len, len));
+            return Ok(self);
+        }
         self.write_byte(b'l')?;
         Ok(self)
     }
@@ -252,16 +398,9 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
     ) -> Result<Self::SerializeTupleVariant> {
         self.serialize_seq(Some(len))
     }
-    #[cfg(feature = "sort_dictionary")]
-    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.write_byte(b'd')?;
-        Ok(StructMapSerializer::new(self, len.unwrap_or(0)))
-    }
-
-    #[cfg(not(feature = "sort_dictionary"))]
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
         self.write_byte(b'd')?;
-        Ok(self)
+        Ok(StructMapSerializer::new(self))
     }
 
     fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
@@ -278,10 +417,10 @@ impl<'s, W: Write> ser::Serializer for &'s mut Serializer<W> {
         self.write_byte(b'd')?;
         variant.serialize(&mut *self)?;
         self.write_byte(b'd')?;
-        Ok(self)
+        Ok(StructMapSerializer::new_variant(self))
     }
 }
-impl<'s, W: Write> ser::SerializeSeq for &'s mut Serializer<W> {
+impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -294,10 +433,10 @@ impl<'s, W: Write> ser::SerializeSeq for &'s mut Serializer<W> {
     }
 
     fn end(self) -> Result<Self::Ok> {
-        self.write_byte(b'e')
+        self.end_seq()
     }
 }
-impl<'s, W: Write> ser::SerializeTuple for &'s mut Serializer<W> {
+impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -313,7 +452,7 @@ impl<'s, W: Write> ser::SerializeTuple for &'s mut Serializer<W> {
         self.write_byte(b'e')
     }
 }
-impl<'s, W: Write> ser::SerializeTupleStruct for &'s mut Serializer<W> {
+impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -329,7 +468,7 @@ impl<'s, W: Write> ser::SerializeTupleStruct for &'s mut Serializer<W> {
         self.write_byte(b'e')
     }
 }
-impl<'s, W: Write> ser::SerializeTupleVariant for &'s mut Serializer<W> {
+impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -345,111 +484,302 @@ impl<'s, W: Write> ser::SerializeTupleVariant for &'s mut Serializer<W> {
         self.write_byte(b'e')
     }
 }
-#[cfg(not(feature = "sort_dictionary"))]
-impl<'s, W: Write> ser::SerializeMap for &'s mut Serializer<W> {
-    type Ok = ();
+mod dict_serializer {
+    use super::*;
+    use std::{
+        cmp::Ordering,
+        collections::{hash_map::RandomState, HashSet},
+        hash::BuildHasher,
+        ops::Range,
+    };
 
-    type Error = Error;
+    /// Entry of a dictionary in [`Scratch::bytes`]
+    struct Entry {
+        /// Index of the dictionary in [`Scratch::dicts`]
+        dict: usize,
+        /// Start of the encoded key, the value follows it up to `end`
+        start: usize,
+        /// Key without the length, entries are sorted by it
+        name: Range<usize>,
+        end: usize,
+    }
+
+    /// Arena shared by all dictionaries of a document that are being
+    /// sorted. Nested dictionaries are written in place and only their
+    /// entries are reordered, the bytes are copied to the writer once when
+    /// the outermost dictionary ends. Buffers are reused between documents.
+    #[derive(Default)]
+    pub(super) struct Scratch {
+        pub(super) bytes: Vec<u8>,
+        /// Entries of dictionaries between `d` and `e`, in order of start
+        dicts: Vec<Range<usize>>,
+        entries: Vec<Entry>,
+        /// Number of dictionaries being written
+        pub(super) depth: usize,
+        /// Hashes of keys of each dictionary written without sorting, sets
+        /// after the first `unsorted` ones are kept to be reused
+        seen: Vec<HashSet<u64>>,
+        hasher: RandomState,
+        /// Encoded keys of dictionaries written without sorting, keys of a
+        /// nested dictionary follow the ones of its parent. Keys with the
+        /// same hash are compared here
+        keys: Vec<u8>,
+        /// Number of dictionaries being written without sorting
+        unsorted: usize,
+    }
+
+    impl Scratch {
+        /// Sorts the entries and writes the outermost dictionary without
+        /// `d` and `e`
+        fn write_sorted<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+            let bytes = &self.bytes;
+            self.entries.sort_unstable_by(|a, b| {
+                a.dict
+                    .cmp(&b.dict)
+                    .then_with(|| bytes[a.name.clone()].cmp(&bytes[b.name.clone()]))
+            });
+            let duplicate = self.entries.windows(2).find(|pair| {
+                pair[0].dict == pair[1].dict
+                    && bytes[pair[0].name.clone()] == bytes[pair[1].name.clone()]
+            });
+            if let Some(pair) = duplicate {
+                return Err(Error::DuplicateKey(bytes[pair[0].name.clone()].to_vec()));
+            }
+            self.write_dict(0, writer)
+        }
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        key.serialize(&mut only_string_ser::OnlyStringSerializer { ser: self })?;
-        Ok(())
-    }
+        fn write_dict<W: Write>(&self, dict: usize, writer: &mut W) -> Result<()> {
+            let start = self.entries.partition_point(|e| e.dict < dict);
+            let end = self.entries.partition_point(|e| e.dict <= dict);
+            for entry in &self.entries[start..end] {
+                self.write_range(entry.start..entry.end, writer)?;
+            }
+            Ok(())
+        }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        value.serialize(&mut **self)
+        /// Writes `range` of the arena, sorting dictionaries nested in it
+        fn write_range<W: Write>(&self, range: Range<usize>, writer: &mut W) -> Result<()> {
+            let mut pos = range.start;
+            // dictionaries that start at or before `pos` are the ones `range`
+            // is inside of or were written already
+            let mut next = self.dicts.partition_point(|d| d.start <= pos);
+            while let Some(dict) = self.dicts.get(next).filter(|d| d.start < range.end) {
+                writer.write_all(&self.bytes[pos..dict.start])?;
+                self.write_dict(next, writer)?;
+                pos = dict.end;
+                next = self.dicts.partition_point(|d| d.start <= pos);
+            }
+            writer.write_all(&self.bytes[pos..range.end])?;
+            Ok(())
+        }
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        self.write_byte(b'e')
+    /// Serializes dictionaries, writes entries to the scratch arena to sort
+    /// them if [`SerializerOptions::sort_keys`] is set or straight to the
+    /// parent otherwise
+    pub struct StructMapSerializer<'s, T: Write> {
+        parent: &'s mut Serializer<T>,
+        /// Index of the dictionary in [`Scratch::dicts`] if it's sorted
+        dict: Option<usize>,
+        /// Index of the entry whose value is written next
+        entry: usize,
+        /// Number of entries in [`Scratch::entries`] before the dictionary
+        entries: usize,
+        /// Index of the set of keys in [`Scratch::seen`] if it's not sorted
+        seen: usize,
+        /// Start of the keys of the dictionary in [`Scratch::keys`]
+        keys: usize,
+        /// Start of the last key in [`Scratch::keys`]
+        last: Option<usize>,
+        /// Fields of a struct variant, inside of `d<variant>`
+        variant: bool,
+        /// [`end`](StructMapSerializer::end) was called
+        ended: bool,
+    }
+
+    /// Returns the key without the length
+    fn key_name(key: &[u8]) -> &[u8] {
+        match key.iter().position(|x| *x == b':') {
+            Some(colon) => &key[colon + 1..],
+            None => key,
+        }
     }
-}
-#[cfg(not(feature = "sort_dictionary"))]
-impl<'s, W: Write> ser::SerializeStruct for &'s mut Serializer<W> {
-    type Ok = ();
 
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)?;
-        Ok(())
+    /// Splits encoded keys that follow each other
+    fn split_keys(mut keys: &[u8]) -> impl Iterator<Item = &[u8]> {
+        std::iter::from_fn(move || {
+            let colon = keys.iter().position(|x| *x == b':')?;
+            let len: usize = btoi::btou(&keys[..colon]).ok()?;
+            let (key, rest) = keys.split_at(colon + 1 + len);
+            keys = rest;
+            Some(key)
+        })
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        self.write_byte(b'e')
-    }
-}
-impl<'s, W: Write> ser::SerializeStructVariant for &'s mut Serializer<W> {
-    type Ok = ();
+    impl<'s, T: Write> StructMapSerializer<'s, T> {
+        pub(super) fn new(parent: &'s mut Serializer<T>) -> Self {
+            let seen = parent.scratch.unsorted;
+            let dict = if parent.options.sort_keys {
+                let scratch = &mut parent.scratch;
+                if scratch.depth == 0 {
+                    scratch.bytes.clear();
+                    scratch.dicts.clear();
+                    scratch.entries.clear();
+                }
+                scratch.depth += 1;
+                let start = scratch.bytes.len();
+                scratch.dicts.push(start..start);
+                Some(scratch.dicts.len() - 1)
+            } else {
+                let scratch = &mut parent.scratch;
+                match scratch.seen.get_mut(seen) {
+                    Some(hashes) => hashes.clear(),
+                    None => scratch.seen.push(HashSet::new()),
+                }
+                scratch.unsorted += 1;
+                None
+            };
+            StructMapSerializer {
+                seen,
+                keys: parent.scratch.keys.len(),
+                last: None,
+                entries: parent.scratch.entries.len(),
+                parent,
+                dict,
+                entry: 0,
+                variant: false,
+                ended: false,
+            }
+        }
 
-    type Error = Error;
+        pub(super) fn new_variant(parent: &'s mut Serializer<T>) -> Self {
+            let mut ser = StructMapSerializer::new(parent);
+            ser.variant = true;
+            ser
+        }
 
-    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
-    where
-        T: Serialize + ?Sized,
-    {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)?;
-        Ok(())
-    }
+        /// Writes a key, duplicates are found when the dictionary ends if
+        /// it's sorted or right away otherwise
+        fn write_key<K>(&mut self, key: &K) -> Result<()>
+        where
+            K: Serialize + ?Sized,
+        {
+            let Some(dict) = self.dict else {
+                return self.write_unsorted_key(key);
+            };
+            let start = self.parent.scratch.bytes.len();
+            key.serialize(&mut only_string_ser::OnlyStringSerializer {
+                ser: &mut *self.parent,
+            })?;
+            let scratch = &mut self.parent.scratch;
+            let end = scratch.bytes.len();
+            // the length is found once here, not on every comparison
+            let name_len = key_name(&scratch.bytes[start..]).len();
+            self.entry = scratch.entries.len();
+            scratch.entries.push(Entry {
+                dict,
+                start,
+                name: end - name_len..end,
+                end,
+            });
+            Ok(())
+        }
 
-    fn end(self) -> Result<Self::Ok> {
-        self.write_byte(b'e')?;
-        self.write_byte(b'e')?;
-        Ok(())
-    }
-}
+        /// Keeps a copy of the key to compare it with the next ones and
+        /// writes it to the parent
+        fn write_unsorted_key<K>(&mut self, key: &K) -> Result<()>
+        where
+            K: Serialize + ?Sized,
+        {
+            let mut keys = std::mem::take(&mut self.parent.scratch.keys);
+            let start = keys.len();
+            let ret = key
+                .serialize(&mut only_string_ser::OnlyStringSerializer {
+                    ser: &mut Serializer::with_options(&mut keys, self.parent.options),
+                })
+                .and_then(|()| self.check_unsorted_key(&keys, start))
+                .and_then(|()| {
+                    self.parent.writer.write_all(&keys[start..])?;
+                    Ok(())
+                });
+            self.parent.scratch.keys = keys;
+            ret
+        }
 
-#[cfg(feature = "sort_dictionary")]
-mod dict_serializer {
-    use super::*;
-    pub struct StructMapSerializer<'s, T: Write> {
-        keys: Vec<Vec<u8>>,
-        values: Vec<Vec<u8>>,
-        parent: &'s mut Serializer<T>,
-    }
+        /// Checks the key at `start` of `keys` is not a duplicate, or is
+        /// greater than the previous one if [`Serializer::require_sorted`]
+        /// is set
+        fn check_unsorted_key(&mut self, keys: &[u8], start: usize) -> Result<()> {
+            let key = &keys[start..];
+            if self.parent.require_sorted {
+                if let Some(last) = self.last {
+                    match key_name(key).cmp(key_name(&keys[last..start])) {
+                        Ordering::Less => return Err(Error::UnsortedKey(key_name(key).to_vec())),
+                        Ordering::Equal => return Err(Error::DuplicateKey(key_name(key).to_vec())),
+                        Ordering::Greater => {}
+                    }
+                }
+            } else {
+                let scratch = &mut self.parent.scratch;
+                let hash = scratch.hasher.hash_one(key);
+                // a known hash is a duplicate unless the keys collide
+                if !scratch.seen[self.seen].insert(hash)
+                    && split_keys(&keys[self.keys..start]).any(|k| k == key)
+                {
+                    return Err(Error::DuplicateKey(key_name(key).to_vec()));
+                }
+            }
+            self.last = Some(start);
+            Ok(())
+        }
 
-    impl<'s, T: Write> StructMapSerializer<'s, T> {
-        pub(super) fn new(parent: &'s mut Serializer<T>, len: usize) -> Self {
-            StructMapSerializer {
-                keys: Vec::with_capacity(len),
-                values: Vec::with_capacity(len),
-                parent,
+        /// Ends the entry added last, its value was written
+        fn end_entry(&mut self) {
+            if self.dict.is_some() {
+                let scratch = &mut self.parent.scratch;
+                scratch.entries[self.entry].end = scratch.bytes.len();
             }
         }
 
-        fn sort_and_write_to_parent(mut self) -> Result<()> {
-            let mut map = self
-                .keys
-                .iter_mut()
-                .zip(self.values.iter_mut())
-                .collect::<Vec<_>>();
-            // seems we need to skip bencode header of the string
-            map.sort_by(|(k1, _), (k2, _)| {
-                // if we are here than keys should already be valid bencode strings
-                // so it's safe to unwrap
-                let k1s = k1.iter().position(|x| *x == b':').unwrap() + 1;
-                let k2s = k2.iter().position(|x| *x == b':').unwrap() + 1;
-                k1[k1s..].cmp(&k2[k2s..])
-            });
-            for (key, value) in map {
-                self.parent.writer.write_all(key)?;
-                self.parent.writer.write_all(value)?;
+        fn end(mut self) -> Result<()> {
+            self.ended = true;
+            if let Some(dict) = self.dict {
+                let scratch = &mut self.parent.scratch;
+                scratch.dicts[dict].end = scratch.bytes.len();
+                scratch.depth -= 1;
+                if scratch.depth == 0 {
+                    scratch.write_sorted(&mut self.parent.writer)?;
+                }
+            } else {
+                self.parent.scratch.unsorted = self.seen;
+                self.parent.scratch.keys.truncate(self.keys);
             }
             self.parent.write_byte(b'e')?;
+            if self.variant {
+                self.parent.write_byte(b'e')?;
+            }
             Ok(())
         }
     }
+
+    impl<T: Write> Drop for StructMapSerializer<'_, T> {
+        /// Forgets the dictionary if serializing it failed, so the next
+        /// value isn't written to the scratch arena
+        fn drop(&mut self) {
+            if self.ended {
+                return;
+            }
+            let scratch = &mut self.parent.scratch;
+            if let Some(dict) = self.dict {
+                scratch.depth -= 1;
+                scratch.dicts.truncate(dict);
+                scratch.entries.truncate(self.entries);
+            } else {
+                scratch.unsorted = self.seen;
+                scratch.keys.truncate(self.keys);
+            }
+        }
+    }
     impl<'s, W: Write> ser::SerializeMap for StructMapSerializer<'s, W> {
         type Ok = ();
 
@@ -459,26 +789,20 @@ mod dict_serializer {
         where
             T: Serialize + ?Sized,
         {
-            let mut v = Vec::new();
-            let mut temp_ser = Serializer::new(&mut v);
-            key.serialize(&mut only_string_ser::OnlyStringSerializer { ser: &mut temp_ser })?;
-            self.keys.push(v);
-            Ok(())
+            self.write_key(key)
         }
 
         fn serialize_value<T>(&mut self, value: &T) -> Result<()>
         where
             T: Serialize + ?Sized,
         {
-            let mut v = Vec::new();
-            let mut temp_ser = Serializer::new(&mut v);
-            value.serialize(&mut temp_ser)?;
-            self.values.push(v);
+            value.serialize(&mut *self.parent)?;
+            self.end_entry();
             Ok(())
         }
 
         fn end(self) -> Result<Self::Ok> {
-            self.sort_and_write_to_parent()
+            StructMapSerializer::end(self)
         }
     }
     impl<'s, W: Write> ser::SerializeStruct for StructMapSerializer<'s, W> {
@@ -490,30 +814,34 @@ mod dict_serializer {
         where
             T: Serialize + ?Sized,
         {
-            let key = {
-                let mut buf = Vec::new();
-                let mut temp_ser = Serializer::new(&mut buf);
-                key.serialize(&mut temp_ser)?;
-                buf
-            };
-            let value = {
-                let mut buf = Vec::new();
-                let mut temp_ser = Serializer::new(&mut buf);
-                value.serialize(&mut temp_ser)?;
-                buf
-            };
-            self.keys.push(key);
-            self.values.push(value);
+            self.write_key(key)?;
+            value.serialize(&mut *self.parent)?;
+            self.end_entry();
             Ok(())
         }
 
         fn end(self) -> Result<Self::Ok> {
-            self.sort_and_write_to_parent()
+            StructMapSerializer::end(self)
+        }
+    }
+    impl<'s, W: Write> ser::SerializeStructVariant for StructMapSerializer<'s, W> {
+        type Ok = ();
+
+        type Error = super::Error;
+
+        fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+        where
+            T: Serialize + ?Sized,
+        {
+            ser::SerializeStruct::serialize_field(self, key, value)
+        }
+
+        fn end(self) -> Result<Self::Ok> {
+            StructMapSerializer::end(self)
         }
     }
 }
-#[cfg(feature = "sort_dictionary")]
-use dict_serializer::StructMapSerializer;
+use dict_serializer::{Scratch, StructMapSerializer};
 
 #[cfg(test)]
 mod tests {
@@ -538,6 +866,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::manual_str_repeat, clippy::manual_repeat_n)]
     fn strings() -> std::result::Result<(), Box<dyn std::error::Error>> {
         assert_eq!(&to_string(&"abc")?, "3:abc");
         assert_eq!(&to_string(&"")?, "0:");
@@ -548,6 +877,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::manual_repeat_n)]
     fn bytes() -> std::result::Result<(), Box<dyn std::error::Error>> {
         let byte_buf = serde_bytes::ByteBuf::from(vec![b'a', b'b', b'c']);
         assert_eq!(&to_string(&byte_buf)?, "3:abc");
@@ -652,9 +982,10 @@ mod tests {
         assert_eq!(&to_string(&map)?, "d1:ai1e1:bi2e1:ci3ee");
         {
             let mut map2 = HashMap::new();
-            map2.insert(1, 2);
-            map2.insert(2, 3);
-            map2.insert(3, 4);
+            map2.insert((1, 2), 2);
+            assert!(to_string(&map2).is_err());
+            let mut map2 = HashMap::new();
+            map2.insert(true, 2);
             assert!(to_string(&map2).is_err());
         }
 
@@ -669,6 +1000,16 @@ mod tests {
             assert_eq!(&to_string(&map)?, "d1:ai1e1:bi2e1:ci3ee");
             #[cfg(not(feature = "sort_dictionary"))]
             assert_eq!(&to_string(&map)?, "d1:ci3e1:bi2e1:ai1ee");
+
+            for (sort_keys, expected) in [
+                (true, "d1:ai1e1:bi2e1:ci3ee"),
+                (false, "d1:ci3e1:bi2e1:ai1ee"),
+            ] {
+                let options = SerializerOptions { sort_keys };
+                let mut ser = Serializer::with_options(Vec::new(), options);
+                map.serialize(&mut ser)?;
+                assert_eq!(ser.into_inner(), expected.as_bytes());
+            }
         }
 
         Ok(())
@@ -734,4 +1075,251 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn nested_sorting() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        #[derive(Serialize)]
+        struct Inner {
+            z: (),
+            a: Vec<std::collections::HashMap<&'static str, i32>>,
+        }
+        #[derive(Serialize)]
+        struct Outer {
+            list: Vec<Inner>,
+            empty: Vec<()>,
+            b: Inner,
+        }
+        let inner = || Inner {
+            z: (),
+            a: vec![[("y", 1), ("x", 2)].into(), Default::default()],
+        };
+        let value = Outer {
+            list: vec![inner(), inner()],
+            empty: vec![],
+            b: inner(),
+        };
+        let options = SerializerOptions { sort_keys: true };
+        let mut ser = Serializer::with_options(Vec::new(), options);
+        value.serialize(&mut ser)?;
+        let inner = "d1:ald1:xi2e1:yi1eedee1:z0:e";
+        assert_eq!(
+            String::from_utf8(ser.into_inner())?,
+            format!("d1:b{0}5:emptyle4:listl{0}{0}ee", inner)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        use std::collections::BTreeMap;
+        #[derive(Serialize)]
+        struct Flatten {
+            name: &'static str,
+            #[serde(flatten)]
+            extra: BTreeMap<&'static str, i32>,
+        }
+        let value = Flatten {
+            name: "x",
+            extra: [("a", 1), ("name", 2)].into(),
+        };
+        let nested = BTreeMap::from([("list", vec![&value])]);
+        for sort_keys in [true, false] {
+            let options = SerializerOptions { sort_keys };
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            assert_eq!(
+                value.serialize(&mut ser),
+                Err(Error::DuplicateKey(b"name".to_vec()))
+            );
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            assert_eq!(
+                nested.serialize(&mut ser),
+                Err(Error::DuplicateKey(b"name".to_vec()))
+            );
+        }
+
+        // same keys in different dictionaries
+        let value = BTreeMap::from([("a", BTreeMap::from([("a", BTreeMap::from([("a", 1)]))]))]);
+        for sort_keys in [true, false] {
+            let options = SerializerOptions { sort_keys };
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            value.serialize(&mut ser).unwrap();
+            assert_eq!(ser.into_inner(), b"d1:ad1:ad1:ai1eeee");
+        }
+
+        // keys of a nested dictionary are forgotten when it ends
+        #[derive(Serialize)]
+        struct Nested {
+            a: BTreeMap<&'static str, i32>,
+            #[serde(flatten)]
+            extra: BTreeMap<&'static str, i32>,
+        }
+        let value = Nested {
+            a: [("x", 1)].into(),
+            extra: [("x", 2)].into(),
+        };
+        let options = SerializerOptions { sort_keys: false };
+        let mut ser = Serializer::with_options(Vec::new(), options);
+        value.serialize(&mut ser).unwrap();
+        assert_eq!(ser.into_inner(), b"d1:ad1:xi1ee1:xi2ee");
+        let value = Nested {
+            a: [("x", 1)].into(),
+            extra: [("a", 2)].into(),
+        };
+        assert_eq!(
+            value.serialize(&mut Serializer::with_options(Vec::new(), options)),
+            Err(Error::DuplicateKey(b"a".to_vec()))
+        );
+    }
+
+    #[test]
+    fn reuse_after_error() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use std::collections::BTreeMap;
+        let flat = BTreeMap::from([("a", None::<i32>)]);
+        let nested = BTreeMap::from([("a", BTreeMap::from([("b", None::<i32>)]))]);
+        for sort_keys in [true, false] {
+            let options = SerializerOptions { sort_keys };
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            assert_eq!(flat.serialize(&mut ser), Err(Error::NoneNotSupported));
+            assert_eq!(nested.serialize(&mut ser), Err(Error::NoneNotSupported));
+            5.serialize(&mut ser)?;
+            BTreeMap::from([("b", 1)]).serialize(&mut ser)?;
+            assert!(ser.into_inner().ends_with(b"i5ed1:bi1ee"));
+        }
+
+        let mut ser = Serializer::new(Vec::new());
+        let long = ReaderBytes::new(2, &b"abc"[..]);
+        assert_eq!(long.serialize(&mut ser), Err(Error::ReaderBytesLength(2)));
+        let short = ReaderBytes::new(4, &b"abc"[..]);
+        assert_eq!(short.serialize(&mut ser), Err(Error::ReaderBytesLength(4)));
+        "xy".serialize(&mut ser)?;
+        vec![1].serialize(&mut ser)?;
+        assert!(ser.into_inner().ends_with(b"2:xyli1ee"));
+        Ok(())
+    }
+
+    #[test]
+    fn struct_variant() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use serde::Deserialize;
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum FileMode {
+            SingleFile {
+                length: u64,
+                md5sum: serde_bytes::ByteBuf,
+            },
+            MultipleFiles {
+                name: String,
+                files: Vec<FileMode>,
+            },
+        }
+        let value = FileMode::MultipleFiles {
+            name: "dir".to_owned(),
+            files: vec![FileMode::SingleFile {
+                length: 1,
+                md5sum: serde_bytes::ByteBuf::from(b"ab".to_vec()),
+            }],
+        };
+        let options = SerializerOptions { sort_keys: true };
+        let mut ser = Serializer::with_options(Vec::new(), options);
+        value.serialize(&mut ser)?;
+        let encoded = ser.into_inner();
+        assert_eq!(
+            encoded,
+            b"d13:MultipleFilesd5:filesld10:SingleFiled6:lengthi1e6:md5sum2:abeee4:name3:diree"
+        );
+        // strict mode rejects unsorted keys
+        assert_eq!(crate::from_bytes_strict::<FileMode>(&encoded)?, value);
+
+        let options = SerializerOptions { sort_keys: false };
+        let mut ser = Serializer::with_options(Vec::new(), options);
+        value.serialize(&mut ser)?;
+        let encoded = ser.into_inner();
+        assert_eq!(
+            encoded,
+            b"d13:MultipleFilesd4:name3:dir5:filesld10:SingleFiled6:lengthi1e6:md5sum2:abeeeee"
+        );
+        assert_eq!(crate::from_bytes::<FileMode>(&encoded)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn struct_variant_duplicate_keys() {
+        use ser::SerializeStructVariant;
+        struct Twice;
+        impl Serialize for Twice {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                let mut variant = serializer.serialize_struct_variant("E", 0, "V", 2)?;
+                variant.serialize_field("a", &1)?;
+                variant.serialize_field("a", &2)?;
+                variant.end()
+            }
+        }
+        for sort_keys in [true, false] {
+            let options = SerializerOptions { sort_keys };
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            assert_eq!(
+                Twice.serialize(&mut ser),
+                Err(Error::DuplicateKey(b"a".to_vec()))
+            );
+        }
+    }
+
+    #[test]
+    fn map_keys() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        use serde::Deserialize;
+        use std::collections::BTreeMap;
+        fn sorted<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+            let options = SerializerOptions { sort_keys: true };
+            let mut ser = Serializer::with_options(Vec::new(), options);
+            value.serialize(&mut ser)?;
+            Ok(ser.into_inner())
+        }
+
+        let ints = BTreeMap::from([(2u32, 1), (10, 2), (0, 3)]);
+        let encoded = sorted(&ints)?;
+        // sorted as byte strings
+        assert_eq!(encoded, b"d1:0i3e2:10i2e1:2i1ee");
+        assert_eq!(crate::from_bytes::<BTreeMap<u32, i32>>(&encoded)?, ints);
+        assert_eq!(
+            sorted(&BTreeMap::from([(-1i64, 1), (i64::MIN, 2)]))?,
+            b"d2:-1i1e20:-9223372036854775808i2ee"
+        );
+
+        let hash = serde_bytes::ByteBuf::from(vec![0xff, 0]);
+        assert_eq!(to_vec(&BTreeMap::from([(hash, 1)]))?, b"d2:\xff\0i1ee");
+        assert_eq!(to_string(&BTreeMap::from([('ж', 1)]))?, "d2:жi1ee");
+        let files = BTreeMap::from([([0xffu8; 20], 1), ([0; 20], 2)]);
+        let encoded = sorted(&files)?;
+        assert_eq!(
+            &encoded[..24],
+            b"d20:\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0"
+        );
+        assert_eq!(encoded.len(), 2 * 26 + 2);
+        assert_eq!(
+            crate::from_bytes::<BTreeMap<[u8; 20], i32>>(&encoded)?,
+            files
+        );
+        assert!(crate::from_bytes::<BTreeMap<[u8; 2], i32>>(b"d3:abci1ee").is_err());
+        assert_eq!(
+            to_vec(&BTreeMap::from([((1u8, 'a'), 1)])),
+            Err(Error::DictionaryKeyMustBeString)
+        );
+
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        struct Port(u16);
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        enum Kind {
+            Seed,
+            Leech,
+        }
+        let ports = BTreeMap::from([(Port(80), Kind::Seed), (Port(6881), Kind::Leech)]);
+        let encoded = sorted(&ports)?;
+        assert_eq!(encoded, b"d4:68815:Leech2:804:Seede");
+        assert_eq!(crate::from_bytes::<BTreeMap<Port, Kind>>(&encoded)?, ports);
+        let kinds = BTreeMap::from([(Kind::Leech, 1)]);
+        assert_eq!(to_string(&kinds)?, "d5:Leechi1ee");
+        Ok(())
+    }
 }
@@ -0,0 +1,243 @@
+use std::io::Write;
+
+use super::Serializer;
+use crate::error::{SerError as Error, SerResult as Result};
+
+/// List or dictionary [`Encoder`] is inside of
+struct Frame {
+    dict: bool,
+    /// Dictionary key was written, its value was not
+    in_value: bool,
+    /// Last key of a dictionary, kept only when checking key order
+    key: Option<Vec<u8>>,
+}
+
+/// Writes bencode piece by piece, for data that is not a [`Serialize`](serde::Serialize)
+/// type, like rows streamed from a database.
+///
+/// Integers and byte strings are written the same way [`Serializer`] writes
+/// them. In debug builds or in [strict mode](Encoder::strict) the encoder
+/// checks that lists and dictionaries are nested correctly, every value in
+/// a dictionary has a key, keys are sorted and nothing follows the
+/// top-level value. Otherwise the pieces are
+/// written as they come.
+/// # Examples
+/** ```
+# use serde_bencoded::Encoder;
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let mut encoder = Encoder::new(Vec::new());
+encoder.begin_dict()?;
+encoder.key(b"files")?.begin_list()?;
+for (name, length) in [("a", 1), ("b", 2)] {
+    encoder.begin_dict()?;
+    encoder.key(b"length")?.int(length)?;
+    encoder.key(b"name")?.bytes(name.as_bytes())?;
+    encoder.end()?;
+}
+encoder.end()?.end()?;
+assert_eq!(
+    encoder.finish()?,
+    b"d5:filesld6:lengthi1e4:name1:aed6:lengthi2e4:name1:beee"
+);
+# Ok(())
+# }
+```*/
+pub struct Encoder<W: Write> {
+    ser: Serializer<W>,
+    stack: Vec<Frame>,
+    /// The top-level value is complete
+    done: bool,
+    strict: bool,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W) -> Self {
+        Encoder {
+            ser: Serializer::new(writer),
+            stack: Vec::new(),
+            done: false,
+            strict: false,
+        }
+    }
+
+    /// Enables the checks in release builds too, they are always on in
+    /// debug builds
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Starts a dictionary, write keys and values next and then [`end`](Encoder::end) it
+    pub fn begin_dict(&mut self) -> Result<&mut Self> {
+        self.begin(true)
+    }
+
+    /// Starts a list, write values next and then [`end`](Encoder::end) it
+    pub fn begin_list(&mut self) -> Result<&mut Self> {
+        self.begin(false)
+    }
+
+    /// Writes a dictionary key, the value goes next
+    pub fn key(&mut self, key: &[u8]) -> Result<&mut Self> {
+        let check = self.checks();
+        match self.stack.last_mut() {
+            Some(frame) if frame.dict && !frame.in_value => {
+                if check {
+                    match &frame.key {
+                        Some(last) if last.as_slice() == key => {
+                            return Err(Error::DuplicateKey(key.to_vec()))
+                        }
+                        Some(last) if last.as_slice() > key => {
+                            return Err(Error::UnsortedKey(key.to_vec()))
+                        }
+                        _ => frame.key = Some(key.to_vec()),
+                    }
+                }
+                frame.in_value = true;
+            }
+            _ if check => return Err(Error::UnexpectedKey),
+            _ => {}
+        }
+        self.ser.write_byte_string(key)?;
+        Ok(self)
+    }
+
+    /// Writes an integer
+    pub fn int(&mut self, v: i64) -> Result<&mut Self> {
+        self.value()?;
+        self.ser.write_integer(v)?;
+        self.done = self.stack.is_empty();
+        Ok(self)
+    }
+
+    /// Writes a byte string
+    pub fn bytes(&mut self, v: &[u8]) -> Result<&mut Self> {
+        self.value()?;
+        self.ser.write_byte_string(v)?;
+        self.done = self.stack.is_empty();
+        Ok(self)
+    }
+
+    /// Ends the last list or dictionary that was started
+    pub fn end(&mut self) -> Result<&mut Self> {
+        match self.stack.last() {
+            Some(frame) if !frame.in_value => {
+                self.stack.pop();
+                self.done = self.stack.is_empty();
+            }
+            _ if self.checks() => return Err(Error::UnexpectedEnd),
+            _ => {}
+        }
+        self.ser.write_byte(b'e')?;
+        Ok(self)
+    }
+
+    /// Flushes and returns the writer, checks that every list and
+    /// dictionary is ended
+    pub fn finish(mut self) -> Result<W> {
+        if self.checks() && !self.stack.is_empty() {
+            return Err(Error::Unfinished);
+        }
+        self.ser.writer.flush()?;
+        Ok(self.ser.writer)
+    }
+
+    fn checks(&self) -> bool {
+        self.strict || cfg!(debug_assertions)
+    }
+
+    fn begin(&mut self, dict: bool) -> Result<&mut Self> {
+        self.value()?;
+        self.stack.push(Frame {
+            dict,
+            in_value: false,
+            key: None,
+        });
+        self.ser.write_byte(if dict { b'd' } else { b'l' })?;
+        Ok(self)
+    }
+
+    /// Updates the state before a value is written
+    fn value(&mut self) -> Result<()> {
+        let check = self.checks();
+        match self.stack.last_mut() {
+            Some(frame) if check && frame.dict && !frame.in_value => Err(Error::ExpectedKey),
+            Some(frame) => {
+                frame.in_value = false;
+                Ok(())
+            }
+            None if check && self.done => Err(Error::TrailingValue),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bencode, to_vec};
+
+    #[test]
+    fn encoder() -> Result<()> {
+        let mut encoder = Encoder::new(Vec::new()).strict(true);
+        encoder
+            .begin_dict()?
+            .key(b"a")?
+            .begin_list()?
+            .int(-1)?
+            .bytes(b"")?
+            .begin_dict()?
+            .end()?
+            .end()?
+            .key(b"b")?
+            .int(i64::MAX)?
+            .end()?;
+        let value = bencode! { "a" => [-1, b"", {}], "b" => i64::MAX };
+        assert_eq!(encoder.finish()?, to_vec(&value)?);
+        Ok(())
+    }
+
+    type Calls = fn(&mut Encoder<Vec<u8>>) -> Result<&mut Encoder<Vec<u8>>>;
+
+    #[test]
+    fn errors() -> Result<()> {
+        let err = |f: Calls| {
+            let mut encoder = Encoder::new(Vec::new()).strict(true);
+            f(&mut encoder).err()
+        };
+        assert_eq!(err(|e| e.key(b"a")), Some(Error::UnexpectedKey));
+        assert_eq!(
+            err(|e| e.begin_list()?.key(b"a")),
+            Some(Error::UnexpectedKey)
+        );
+        assert_eq!(
+            err(|e| e.begin_dict()?.key(b"a")?.key(b"b")),
+            Some(Error::UnexpectedKey)
+        );
+        assert_eq!(err(|e| e.begin_dict()?.int(1)), Some(Error::ExpectedKey));
+        assert_eq!(err(|e| e.end()), Some(Error::UnexpectedEnd));
+        assert_eq!(
+            err(|e| e.begin_dict()?.key(b"a")?.end()),
+            Some(Error::UnexpectedEnd)
+        );
+        assert_eq!(
+            err(|e| e.begin_dict()?.key(b"b")?.int(1)?.key(b"a")),
+            Some(Error::UnsortedKey(b"a".to_vec()))
+        );
+        assert_eq!(
+            err(|e| e.begin_dict()?.key(b"a")?.int(1)?.key(b"a")),
+            Some(Error::DuplicateKey(b"a".to_vec()))
+        );
+        assert_eq!(err(|e| e.int(1)?.int(2)), Some(Error::TrailingValue));
+        assert_eq!(
+            err(|e| e.begin_list()?.end()?.begin_dict()),
+            Some(Error::TrailingValue)
+        );
+        assert_eq!(err(|e| e.bytes(b"")?.end()), Some(Error::UnexpectedEnd));
+
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.begin_list()?;
+        assert_eq!(encoder.finish().unwrap_err(), Error::Unfinished);
+        Ok(())
+    }
+}
@@ -3,10 +3,22 @@ use std::io::Write;
 use serde::{ser, Serialize};
 
 use crate::error::SerError as Error;
+
+/// Serializes dictionary keys. Byte strings, chars, unit variants and
+/// newtypes around them are written as byte strings, integers as byte
+/// strings of their decimal digits. Tuples of `u8`, like `[u8; 20]`, are
+/// byte strings of the elements.
 pub(crate) struct OnlyStringSerializer<'s, W: Write> {
     pub(crate) ser: &'s mut super::Serializer<W>,
 }
 
+impl<W: Write> OnlyStringSerializer<'_, W> {
+    fn serialize_integer<I: itoa::Integer>(&mut self, v: I) -> Result<(), Error> {
+        let mut buf = itoa::Buffer::new();
+        self.ser.write_byte_string(buf.format(v).as_bytes())
+    }
+}
+
 impl<'s, W: Write> ser::Serializer for &'s mut OnlyStringSerializer<'s, W> {
     type Ok = ();
 
@@ -30,36 +42,36 @@ impl<'s, W: Write> ser::Serializer for &'s mut OnlyStringSerializer<'s, W> {
         Err(Error::DictionaryKeyMustBeString)
     }
 
-    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_i64(self, _: i64) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
-    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_integer(v)
     }
 
     fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
@@ -70,16 +82,16 @@ impl<'s, W: Write> ser::Serializer for &'s mut OnlyStringSerializer<'s, W> {
         Err(Error::DictionaryKeyMustBeString)
     }
 
-    fn serialize_char(self, _: char) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.ser.serialize_char(v)
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
         self.ser.serialize_str(v)
     }
 
-    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.ser.serialize_bytes(v)
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
@@ -105,16 +117,23 @@ impl<'s, W: Write> ser::Serializer for &'s mut OnlyStringSerializer<'s, W> {
         self,
         _: &'static str,
         _: u32,
-        _: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+        self.ser.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _: &'static str, _: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
     where
         T: Serialize + ?Sized,
     {
-        Err(Error::DictionaryKeyMustBeString)
+        if name == crate::raw::TOKEN || name == super::READER_BYTES_TOKEN {
+            return Err(Error::DictionaryKeyMustBeString);
+        }
+        value.serialize(self)
     }
 
     fn serialize_newtype_variant<T>(
@@ -134,8 +153,9 @@ impl<'s, W: Write> ser::Serializer for &'s mut OnlyStringSerializer<'s, W> {
         Err(Error::DictionaryKeyMustBeString)
     }
 
-    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.ser.write_byte_string_len(len)?;
+        Ok(self)
     }
 
     fn serialize_tuple_struct(
@@ -251,20 +271,22 @@ impl<'s, W: Write> ser::SerializeStructVariant for &'s mut OnlyStringSerializer<
         Err(Error::DictionaryKeyMustBeString)
     }
 }
+/// Bytes of a tuple key follow the length written by `serialize_tuple`
 impl<'s, W: Write> ser::SerializeTuple for &'s mut OnlyStringSerializer<'s, W> {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_element<T>(&mut self, _: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: Serialize + ?Sized,
     {
-        Err(Error::DictionaryKeyMustBeString)
+        let byte = value.serialize(ByteSerializer)?;
+        self.ser.write_byte(byte)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Err(Error::DictionaryKeyMustBeString)
+        Ok(())
     }
 }
 impl<'s, W: Write> ser::SerializeTupleStruct for &'s mut OnlyStringSerializer<'s, W> {
@@ -299,3 +321,169 @@ impl<'s, W: Write> ser::SerializeTupleVariant for &'s mut OnlyStringSerializer<'
         Err(Error::DictionaryKeyMustBeString)
     }
 }
+
+/// Serializes an element of a tuple key, only `u8` is accepted
+pub(crate) struct ByteSerializer;
+
+impl ser::Serializer for ByteSerializer {
+    type Ok = u8;
+
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<u8, Error>;
+
+    type SerializeTuple = ser::Impossible<u8, Error>;
+
+    type SerializeTupleStruct = ser::Impossible<u8, Error>;
+
+    type SerializeTupleVariant = ser::Impossible<u8, Error>;
+
+    type SerializeMap = ser::Impossible<u8, Error>;
+
+    type SerializeStruct = ser::Impossible<u8, Error>;
+
+    type SerializeStructVariant = ser::Impossible<u8, Error>;
+
+    fn serialize_u8(self, v: u8) -> Result<u8, Error> {
+        Ok(v)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_char(self, _: char) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_str(self, _: &str) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_none(self) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_some<T>(self, _: &T) -> Result<u8, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_unit(self) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str) -> Result<u8, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, _: &T) -> Result<u8, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<u8, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+}
@@ -0,0 +1,195 @@
+use std::{cell::RefCell, io::Read};
+
+use serde::{
+    ser::{self, SerializeSeq},
+    Serialize, Serializer,
+};
+
+use crate::error::SerError;
+
+/// Name of the newtype struct [`Serializer`](crate::Serializer) handles
+/// specially for [`ReaderBytes`]
+pub(crate) const TOKEN: &str = "$serde_bencoded::private::ReaderBytes";
+
+/// Size of chunks copied from the reader
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Byte string of a known length read from [`Read`] while it is
+/// serialized, so it is never in memory as a whole.
+///
+/// [`Serializer`](crate::Serializer) copies it to the writer in chunks and
+/// fails with [`SerError::ReaderBytesLength`] if the reader yields a
+/// different number of bytes than `len`. The reader is consumed, so the
+/// value can be serialized once. Values of dictionaries are buffered to
+/// sort them by keys when [`SerializerOptions::sort_keys`](crate::SerializerOptions::sort_keys)
+/// is set.
+///
+/// Other serializers see a sequence of byte strings.
+/// # Examples
+/** ```
+# use serde_bencoded::{to_vec, ReaderBytes};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let file = std::io::Cursor::new(vec![0u8; 100_000]);
+let bytes = ReaderBytes::new(100_000, file);
+let encoded = to_vec(&("data", bytes))?;
+assert_eq!(&encoded[..16], b"l4:data100000:\0\0");
+assert_eq!(encoded.len(), 100_015);
+# Ok(())
+# }
+```*/
+pub struct ReaderBytes<R> {
+    len: usize,
+    reader: RefCell<R>,
+}
+
+impl<R: Read> ReaderBytes<R> {
+    /// `len` is the number of bytes `reader` yields
+    pub fn new(len: usize, reader: R) -> Self {
+        ReaderBytes {
+            len,
+            reader: RefCell::new(reader),
+        }
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: Read> Serialize for ReaderBytes<R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &Chunks(self))
+    }
+}
+
+/// Sequence of chunks of [`ReaderBytes`]
+struct Chunks<'a, R>(&'a ReaderBytes<R>);
+
+impl<R: Read> Serialize for Chunks<'_, R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let len = self.0.len;
+        let mut reader = self.0.reader.borrow_mut();
+        // one byte more to notice a longer reader
+        let mut reader = (&mut *reader).take((len as u64).saturating_add(1));
+        let mut buf = vec![0; CHUNK_SIZE.min(len.saturating_add(1))];
+        let mut read = 0;
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    read += n;
+                    seq.serialize_element(&Chunk(&buf[..n]))?;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                // `Serializer` reports it as `SerError::Io`
+                Err(e) => return Err(ser::Error::custom(e)),
+            }
+        }
+        let ok = seq.end()?;
+        if read != len {
+            return Err(ser::Error::custom(SerError::ReaderBytesLength(len)));
+        }
+        Ok(ok)
+    }
+}
+
+struct Chunk<'a>(&'a [u8]);
+
+impl Serialize for Chunk<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{to_value, to_vec, Value};
+
+    /// Yields `len` bytes at most 3 at a time
+    struct Trickle {
+        len: usize,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.len).min(3);
+            buf[..n].fill(b'x');
+            self.len -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn reader_bytes() -> Result<(), SerError> {
+        let bytes = ReaderBytes::new(7, Trickle { len: 7 });
+        assert_eq!(to_vec(&bytes)?, b"7:xxxxxxx");
+        let bytes = ReaderBytes::new(0, &b""[..]);
+        assert_eq!(to_vec(&vec![bytes])?, b"l0:e");
+        let bytes = ReaderBytes::new(
+            CHUNK_SIZE * 2,
+            Trickle {
+                len: CHUNK_SIZE * 2,
+            },
+        );
+        assert_eq!(to_vec(&bytes)?.len(), CHUNK_SIZE * 2 + 7);
+        let bytes = ReaderBytes::new(3, &b"abc"[..]);
+        assert_eq!(to_value(&bytes)?, Value::Bytes(b"abc".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn length_mismatch() {
+        let bytes = ReaderBytes::new(8, Trickle { len: 7 });
+        assert_eq!(to_vec(&bytes), Err(SerError::ReaderBytesLength(8)));
+        let bytes = ReaderBytes::new(6, Trickle { len: 7 });
+        assert_eq!(to_vec(&bytes), Err(SerError::ReaderBytesLength(6)));
+        let bytes = ReaderBytes::new(3, &b"ab"[..]);
+        assert!(to_value(&bytes).is_err());
+        // the reader is consumed
+        let bytes = ReaderBytes::new(3, &b"abc"[..]);
+        assert!(to_vec(&bytes).is_ok());
+        assert_eq!(to_vec(&bytes), Err(SerError::ReaderBytesLength(3)));
+    }
+
+    /// Fails after `len` bytes
+    struct Broken {
+        len: usize,
+    }
+
+    impl Read for Broken {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.len == 0 {
+                return Err(std::io::Error::other("disk is gone"));
+            }
+            let n = buf.len().min(self.len);
+            self.len -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn reader_error() {
+        let bytes = ReaderBytes::new(8, Broken { len: 4 });
+        assert_eq!(
+            to_vec(&("a", bytes)),
+            Err(SerError::Io("disk is gone".to_string()))
+        );
+        let bytes = ReaderBytes::new(8, Broken { len: 4 });
+        assert_eq!(
+            to_value(&bytes),
+            Err(SerError::Message("disk is gone".to_string()))
+        );
+    }
+}
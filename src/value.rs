@@ -0,0 +1,730 @@
+//! Dynamically typed bencode values.
+
+use std::collections::{btree_map::Entry, BTreeMap};
+use std::fmt;
+
+use serde::{
+    de::{DeserializeOwned, MapAccess, SeqAccess, Unexpected, Visitor},
+    ser::{SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::error::{DeError, DeResult, SerResult};
+
+mod de;
+mod ser;
+
+/// Converts rust's type to [`Value`] without encoding it to bytes.
+/// Types are mapped the same way as with [`to_vec`](crate::to_vec), except
+/// that integers must fit into [`Value::Integer`], so a `u64` above
+/// `i64::MAX` is an error while [`to_vec`](crate::to_vec) encodes it.
+/// # Examples
+/** ```
+# use serde_bencoded::{to_value, Value};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+#[derive(serde::Serialize)]
+struct Info {
+    name: String,
+    length: u64,
+}
+let value = to_value(&Info { name: "x".to_string(), length: 5 })?;
+assert_eq!(value.get("length"), Some(&Value::Integer(5)));
+# Ok(())
+# }
+```*/
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> SerResult<Value> {
+    value.serialize(ser::ValueSerializer)
+}
+
+/// Converts [`Value`] to rust's type without decoding it from bytes.
+/// Byte strings are handled like [`from_bytes`](crate::from_bytes) does.
+/// # Examples
+/** ```
+# use serde_bencoded::{from_value, to_value};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Info {
+    name: String,
+    length: u64,
+}
+let mut value = to_value(&Info { name: "x".to_string(), length: 5 })?;
+*value.get_mut("length").unwrap() = to_value(&10)?;
+assert_eq!(from_value::<Info>(value)?, Info { name: "x".to_string(), length: 10 });
+# Ok(())
+# }
+```*/
+pub fn from_value<T: DeserializeOwned>(value: Value) -> DeResult<T> {
+    T::deserialize(value)
+}
+
+/// Owned representation of any bencode document.
+///
+/// Dictionary keys are raw byte strings kept in a [`BTreeMap`], so iterating
+/// over a dictionary (and serializing it) yields keys in canonical order.
+/// A dictionary with a repeated key is an error, even outside of
+/// [strict mode](crate::Deserializer::strict), since one of the values
+/// would be lost.
+/// # Examples
+/** ```
+# use serde_bencoded::{from_bytes, to_vec, Value};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let value: Value = from_bytes(b"d4:infod6:lengthi5e4:name1:xee")?;
+let info = value.get("info").unwrap();
+assert_eq!(info.get("length").and_then(Value::as_int), Some(5));
+assert_eq!(info.get("name").and_then(Value::as_str), Some("x"));
+assert_eq!(to_vec(&value)?, b"d4:infod6:lengthi5e4:name1:xee");
+# Ok(())
+# }
+```*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// Bencode doesn't bound integers, but only the ones in the range of
+    /// `i64` are representable here
+    Integer(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    /// Returns the integer if `self` is [`Value::Integer`]
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string if `self` is [`Value::Bytes`]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string if `self` is [`Value::Bytes`] and it
+    /// is valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    /// Returns the elements if `self` is [`Value::List`]
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of [`as_list`](Value::as_list)
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if `self` is [`Value::Dict`]
+    pub fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of [`as_dict`](Value::as_dict)
+    pub fn as_dict_mut(&mut self) -> Option<&mut BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if `self` is [`Value::Dict`]
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&Value> {
+        self.as_dict().and_then(|d| d.get(key.as_ref()))
+    }
+
+    /// Mutable version of [`get`](Value::get)
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut Value> {
+        self.as_dict_mut().and_then(|d| d.get_mut(key.as_ref()))
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            Value::Bytes(b) => serializer.serialize_bytes(b),
+            Value::List(l) => {
+                let mut seq = serializer.serialize_seq(Some(l.len()))?;
+                for v in l {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            Value::Dict(d) => {
+                let mut map = serializer.serialize_map(Some(d.len()))?;
+                for (k, v) in d {
+                    map.serialize_entry(&Key(k), v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Dictionary key, serialized as a byte string
+struct Key<'a>(&'a [u8]);
+
+impl Serialize for Key<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Dictionary key, deserialized from either a string or bytes
+struct KeyBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for KeyBuf {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+        impl Visitor<'_> for KeyVisitor {
+            type Value = KeyBuf;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a byte string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(KeyBuf(v.as_bytes().to_vec()))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(KeyBuf(v.into_bytes()))
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(KeyBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(KeyBuf(v))
+            }
+        }
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ValueVisitor;
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("any bencode value")
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(Value::Integer(v as i64))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(Value::Integer(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .map(Value::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(Value::Bytes(v.as_bytes().to_vec()))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(Value::Bytes(v.into_bytes()))
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(Value::Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(Value::Bytes(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut list = Vec::new();
+                while let Some(v) = seq.next_element()? {
+                    list.push(v);
+                }
+                Ok(Value::List(list))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut dict = BTreeMap::new();
+                while let Some((KeyBuf(k), v)) = map.next_entry()? {
+                    insert(&mut dict, k, v)?;
+                }
+                Ok(Value::Dict(dict))
+            }
+        }
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Borrowed representation of any bencode document.
+///
+/// Byte strings and dictionary keys point into the input slice, so
+/// deserializing a document with large byte strings (like `pieces` of a
+/// `.torrent`) does not copy them. Use [`to_owned`](ValueRef::to_owned)
+/// to get a [`Value`]. Repeated dictionary keys are rejected the same way.
+/// # Examples
+/** ```
+# use serde_bencoded::{from_bytes, ValueRef};
+# fn main() -> Result<(), Box<dyn std::error::Error>>{
+let input = b"d6:pieces4:\x01\x02\x03\x04e";
+let value: ValueRef = from_bytes(input)?;
+let pieces = value.get("pieces").and_then(ValueRef::as_bytes).unwrap();
+assert_eq!(pieces.as_ptr(), input[11..].as_ptr());
+# Ok(())
+# }
+```*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueRef<'de> {
+    Integer(i64),
+    Bytes(&'de [u8]),
+    List(Vec<ValueRef<'de>>),
+    Dict(BTreeMap<&'de [u8], ValueRef<'de>>),
+}
+
+impl<'de> ValueRef<'de> {
+    /// Returns the integer if `self` is [`ValueRef::Integer`]
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            ValueRef::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string if `self` is [`ValueRef::Bytes`]
+    pub fn as_bytes(&self) -> Option<&'de [u8]> {
+        match self {
+            ValueRef::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string if `self` is [`ValueRef::Bytes`] and it
+    /// is valid UTF-8
+    pub fn as_str(&self) -> Option<&'de str> {
+        self.as_bytes().and_then(|b| std::str::from_utf8(b).ok())
+    }
+
+    /// Returns the elements if `self` is [`ValueRef::List`]
+    pub fn as_list(&self) -> Option<&[ValueRef<'de>]> {
+        match self {
+            ValueRef::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if `self` is [`ValueRef::Dict`]
+    pub fn as_dict(&self) -> Option<&BTreeMap<&'de [u8], ValueRef<'de>>> {
+        match self {
+            ValueRef::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if `self` is [`ValueRef::Dict`]
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&ValueRef<'de>> {
+        self.as_dict().and_then(|d| d.get(key.as_ref()))
+    }
+
+    /// Copies borrowed byte strings to get an owned [`Value`]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_owned(&self) -> Value {
+        match self {
+            ValueRef::Integer(i) => Value::Integer(*i),
+            ValueRef::Bytes(b) => Value::Bytes(b.to_vec()),
+            ValueRef::List(l) => Value::List(l.iter().map(ValueRef::to_owned).collect()),
+            ValueRef::Dict(d) => {
+                Value::Dict(d.iter().map(|(k, v)| (k.to_vec(), v.to_owned())).collect())
+            }
+        }
+    }
+}
+
+impl Serialize for ValueRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ValueRef::Integer(i) => serializer.serialize_i64(*i),
+            ValueRef::Bytes(b) => serializer.serialize_bytes(b),
+            ValueRef::List(l) => {
+                let mut seq = serializer.serialize_seq(Some(l.len()))?;
+                for v in l {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            ValueRef::Dict(d) => {
+                let mut map = serializer.serialize_map(Some(d.len()))?;
+                for (k, v) in d {
+                    map.serialize_entry(&Key(k), v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Dictionary key, borrowed from the input
+struct KeyRef<'de>(&'de [u8]);
+
+impl<'de> Deserialize<'de> for KeyRef<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = KeyRef<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a borrowed byte string")
+            }
+
+            fn visit_borrowed_str<E: serde::de::Error>(
+                self,
+                v: &'de str,
+            ) -> Result<Self::Value, E> {
+                Ok(KeyRef(v.as_bytes()))
+            }
+
+            fn visit_borrowed_bytes<E: serde::de::Error>(
+                self,
+                v: &'de [u8],
+            ) -> Result<Self::Value, E> {
+                Ok(KeyRef(v))
+            }
+        }
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueRef<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ValueRefVisitor;
+        impl<'de> Visitor<'de> for ValueRefVisitor {
+            type Value = ValueRef<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("any bencode value with borrowed byte strings")
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(ValueRef::Integer(v as i64))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(ValueRef::Integer(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .map(ValueRef::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_borrowed_str<E: serde::de::Error>(
+                self,
+                v: &'de str,
+            ) -> Result<Self::Value, E> {
+                Ok(ValueRef::Bytes(v.as_bytes()))
+            }
+
+            fn visit_borrowed_bytes<E: serde::de::Error>(
+                self,
+                v: &'de [u8],
+            ) -> Result<Self::Value, E> {
+                Ok(ValueRef::Bytes(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut list = Vec::new();
+                while let Some(v) = seq.next_element()? {
+                    list.push(v);
+                }
+                Ok(ValueRef::List(list))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut dict = BTreeMap::new();
+                while let Some((KeyRef(k), v)) = map.next_entry()? {
+                    insert(&mut dict, k, v)?;
+                }
+                Ok(ValueRef::Dict(dict))
+            }
+        }
+        deserializer.deserialize_any(ValueRefVisitor)
+    }
+}
+
+/// Adds an entry to a deserialized dictionary, fails if the key is already in it
+fn insert<K: AsRef<[u8]> + Ord, V, E: serde::de::Error>(
+    dict: &mut BTreeMap<K, V>,
+    key: K,
+    value: V,
+) -> Result<(), E> {
+    match dict.entry(key) {
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+            Ok(())
+        }
+        Entry::Occupied(entry) => Err(E::custom(DeError::DuplicateKey(
+            entry.key().as_ref().to_vec(),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_bytes, from_bytes_auto, to_vec};
+    type Ret = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[test]
+    fn round_trip() -> Ret {
+        let input: &[u8] = b"d1:ai-1e1:bl1:xi2ee1:cd1:d3:\x00\xFF\x01ee";
+        let value: Value = from_bytes(input)?;
+        assert_eq!(value.get("a").and_then(Value::as_int), Some(-1));
+        let b = value.get("b").and_then(Value::as_list).unwrap();
+        assert_eq!(b[0].as_str(), Some("x"));
+        assert_eq!(b[1].as_int(), Some(2));
+        let d = value.get(b"c").and_then(|c| c.get("d")).unwrap();
+        assert_eq!(d.as_bytes(), Some(&b"\x00\xFF\x01"[..]));
+        assert_eq!(d.as_str(), None);
+        assert_eq!(to_vec(&value)?, input);
+        assert_eq!(from_bytes_auto::<Value>(input)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_order() -> Ret {
+        let value: Value = from_bytes(b"d1:bi2e1:ai1e2:\xFF\x00i3ee")?;
+        let keys = value.as_dict().unwrap().keys().collect::<Vec<_>>();
+        assert_eq!(keys, [&b"a"[..], b"b", b"\xFF\x00"]);
+        assert_eq!(to_vec(&value)?, b"d1:ai1e1:bi2e2:\xFF\x00i3ee");
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let err = from_bytes::<Value>(b"d1:ai1e1:bi2e1:ai3ee").unwrap_err();
+        assert_eq!(err.to_string(), "duplicate dictionary key `a` at byte 13");
+        let err = from_bytes::<ValueRef>(b"ld1:ai1e1:ai1eee").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[0]: duplicate dictionary key `a` at byte 8"
+        );
+    }
+
+    #[test]
+    fn integer_out_of_range() {
+        assert!(from_bytes::<Value>(format!("i{}e", u64::MAX).as_bytes()).is_err());
+    }
+
+    #[test]
+    fn borrowed() -> Ret {
+        let input: &[u8] = b"d4:infod6:pieces3:\x00\xFF\x01e4:listli1e1:xee";
+        let value: ValueRef = from_bytes(input)?;
+        let pieces = value.get("info").and_then(|i| i.get("pieces")).unwrap();
+        assert_eq!(pieces.as_bytes().unwrap().as_ptr(), input[18..].as_ptr());
+        let key = *value.as_dict().unwrap().keys().next().unwrap();
+        assert_eq!(key.as_ptr(), input[3..].as_ptr());
+        assert_eq!(to_vec(&value)?, input);
+        assert_eq!(value.to_owned(), from_bytes::<Value>(input)?);
+        assert_eq!(from_bytes_auto::<ValueRef>(input)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn typed_round_trip() -> Ret {
+        use crate::from_value;
+        use std::collections::HashMap;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Mode {
+            Single { length: u64 },
+            Multi(Vec<String>),
+            Empty,
+        }
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Unit;
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Info {
+            name: String,
+            #[serde(with = "serde_bytes")]
+            pieces: Vec<u8>,
+            private: bool,
+            ch: char,
+            unit: Unit,
+            modes: Vec<Mode>,
+            tuple: (i8, String),
+            map: HashMap<String, i32>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            comment: Option<String>,
+        }
+
+        let info = Info {
+            name: "x".to_string(),
+            pieces: vec![0, 0xFF],
+            private: true,
+            ch: 'ы',
+            unit: Unit,
+            modes: vec![
+                Mode::Single { length: 5 },
+                Mode::Multi(vec!["a".to_string()]),
+                Mode::Empty,
+            ],
+            tuple: (-1, "t".to_string()),
+            map: [("b".to_string(), 2), ("a".to_string(), 1)].into(),
+            comment: None,
+        };
+        let value = to_value(&info)?;
+        #[cfg(feature = "sort_dictionary")]
+        assert_eq!(to_vec(&value)?, to_vec(&info)?);
+        assert_eq!(from_bytes::<Value>(&to_vec(&info)?)?, value);
+        assert_eq!(from_value::<Info>(value)?, info);
+        Ok(())
+    }
+
+    #[test]
+    fn map_keys() -> Ret {
+        use crate::from_value;
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        enum Kind {
+            Peer,
+            Seed,
+        }
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        struct Port(u16);
+
+        let ints = BTreeMap::from([(1u32, 2), (10, 3)]);
+        let value = to_value(&ints)?;
+        assert_eq!(to_vec(&value)?, b"d1:1i2e2:10i3ee");
+        assert_eq!(from_value::<BTreeMap<u32, i32>>(value)?, ints);
+        let negative = BTreeMap::from([(-1i8, 1), (127, 2)]);
+        assert_eq!(
+            from_value::<BTreeMap<i8, u8>>(to_value(&negative)?)?,
+            negative
+        );
+        let chars = BTreeMap::from([('a', 1), ('ы', 2)]);
+        assert_eq!(from_value::<BTreeMap<char, u8>>(to_value(&chars)?)?, chars);
+        let kinds = BTreeMap::from([(Kind::Peer, 1), (Kind::Seed, 2)]);
+        let value = to_value(&kinds)?;
+        assert_eq!(to_vec(&value)?, b"d4:Peeri1e4:Seedi2ee");
+        assert_eq!(from_value::<BTreeMap<Kind, u8>>(value)?, kinds);
+        let ports = BTreeMap::from([(Port(6881), 1)]);
+        assert_eq!(from_value::<BTreeMap<Port, u8>>(to_value(&ports)?)?, ports);
+        let hashes = BTreeMap::from([([7u8; 20], 1)]);
+        let value = to_value(&hashes)?;
+        assert_eq!(value.get([7; 20]), Some(&Value::Integer(1)));
+        assert_eq!(from_value::<BTreeMap<[u8; 20], u8>>(value)?, hashes);
+        assert!(to_value(&BTreeMap::from([([1u16], 1)])).is_err());
+
+        let value = to_value(&BTreeMap::from([("x", 1)]))?;
+        assert!(from_value::<BTreeMap<u32, u8>>(value).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn to_value_errors() {
+        use crate::SerError;
+        use std::collections::HashMap;
+
+        assert_eq!(to_value(&i64::MAX), Ok(Value::Integer(i64::MAX)));
+        assert_eq!(
+            to_value(&u64::MAX),
+            Err(SerError::Message(format!(
+                "integer `{}` does not fit into `i64`",
+                u64::MAX
+            )))
+        );
+        assert_eq!(
+            to_vec(&u64::MAX),
+            Ok(format!("i{}e", u64::MAX).into_bytes())
+        );
+        assert_eq!(to_value(&1.0), Err(SerError::FloatingPointNotSupported));
+        assert_eq!(to_value(&None::<u8>), Err(SerError::NoneNotSupported));
+        let map: HashMap<bool, u8> = [(true, 2)].into();
+        assert_eq!(to_value(&map), Err(SerError::DictionaryKeyMustBeString));
+
+        #[derive(Serialize)]
+        struct Flatten {
+            name: &'static str,
+            #[serde(flatten)]
+            extra: HashMap<&'static str, i32>,
+        }
+        let value = Flatten {
+            name: "x",
+            extra: [("name", 2)].into(),
+        };
+        assert_eq!(
+            to_value(&value),
+            Err(SerError::DuplicateKey(b"name".to_vec()))
+        );
+
+        /// Uses the name of [`ReaderBytes`](crate::ReaderBytes) for a list of integers
+        struct FakeReaderBytes;
+        impl Serialize for FakeReaderBytes {
+            fn serialize<S: Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct(crate::ser::READER_BYTES_TOKEN, &[1])
+            }
+        }
+        assert!(matches!(
+            to_value(&FakeReaderBytes),
+            Err(SerError::Message(_))
+        ));
+    }
+}
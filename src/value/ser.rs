@@ -0,0 +1,572 @@
+use std::collections::{btree_map::Entry, BTreeMap};
+
+use serde::{ser, Serialize};
+
+use super::Value;
+use crate::error::{SerError as Error, SerResult as Result};
+
+/// Serializer that builds a [`Value`] instead of writing bytes. Types are
+/// mapped the same way [`Serializer`](crate::Serializer) maps them.
+pub(crate) struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+
+    type SerializeTuple = SerializeList;
+
+    type SerializeTupleStruct = SerializeList;
+
+    type SerializeTupleVariant = SerializeList;
+
+    type SerializeMap = SerializeDict;
+
+    type SerializeStruct = SerializeDict;
+
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        i64::try_from(v)
+            .map(Value::Integer)
+            .map_err(|_| ser::Error::custom(format!("integer `{}` does not fit into `i64`", v)))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Value> {
+        Err(Error::FloatingPointNotSupported)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Value> {
+        Err(Error::FloatingPointNotSupported)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        let mut buf = [0u8; 4];
+        self.serialize_bytes(v.encode_utf8(&mut buf).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Err(Error::NoneNotSupported)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Bytes(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Value> {
+        self.serialize_str(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Value>
+    where
+        T: Serialize + ?Sized,
+    {
+        match value.serialize(self)? {
+            Value::Bytes(raw) if name == crate::raw::TOKEN => {
+                crate::from_bytes(&raw).map_err(|e| Error::Message(e.to_string()))
+            }
+            Value::List(chunks) if name == crate::ser::READER_BYTES_TOKEN => {
+                let mut bytes = Vec::new();
+                for chunk in chunks {
+                    match chunk {
+                        Value::Bytes(chunk) => bytes.extend_from_slice(&chunk),
+                        _ => {
+                            return Err(Error::Message(
+                                "`ReaderBytes` chunk is not a byte string".to_string(),
+                            ))
+                        }
+                    }
+                }
+                Ok(Value::Bytes(bytes))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut dict = BTreeMap::new();
+        dict.insert(variant.as_bytes().to_vec(), value.serialize(self)?);
+        Ok(Value::Dict(dict))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeDict {
+            dict: BTreeMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeStructVariant {
+            variant,
+            dict: BTreeMap::new(),
+        })
+    }
+}
+
+pub(crate) struct SerializeList {
+    list: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.list.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::List(self.list))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Adds an entry to a dictionary, fails if the key is already in it
+fn insert(dict: &mut BTreeMap<Vec<u8>, Value>, key: Vec<u8>, value: Value) -> Result<()> {
+    match dict.entry(key) {
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+            Ok(())
+        }
+        Entry::Occupied(entry) => Err(Error::DuplicateKey(entry.key().clone())),
+    }
+}
+
+pub(crate) struct SerializeDict {
+    dict: BTreeMap<Vec<u8>, Value>,
+    next_key: Option<Vec<u8>>,
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        insert(&mut self.dict, key, value.serialize(ValueSerializer)?)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Dict(self.dict))
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(ValueSerializer)?;
+        insert(&mut self.dict, key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+pub(crate) struct SerializeStructVariant {
+    variant: &'static str,
+    dict: BTreeMap<Vec<u8>, Value>,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(ValueSerializer)?;
+        insert(&mut self.dict, key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Value> {
+        let mut outer = BTreeMap::new();
+        outer.insert(self.variant.as_bytes().to_vec(), Value::Dict(self.dict));
+        Ok(Value::Dict(outer))
+    }
+}
+
+/// Serializes dictionary keys the same way [`Serializer`](crate::Serializer)
+/// does, integers become byte strings of their decimal digits
+struct KeySerializer;
+
+/// Collects the bytes of a tuple key
+struct SerializeKeyBytes(Vec<u8>);
+
+impl ser::SerializeTuple for SerializeKeyBytes {
+    type Ok = Vec<u8>;
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.0.push(value.serialize(crate::ser::ByteSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        Ok(self.0)
+    }
+}
+
+/// Returns the decimal digits of an integer key
+fn integer_key<I: itoa::Integer>(v: I) -> Result<Vec<u8>> {
+    Ok(itoa::Buffer::new().format(v).as_bytes().to_vec())
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<Vec<u8>, Error>;
+
+    type SerializeTuple = SerializeKeyBytes;
+
+    type SerializeTupleStruct = ser::Impossible<Vec<u8>, Error>;
+
+    type SerializeTupleVariant = ser::Impossible<Vec<u8>, Error>;
+
+    type SerializeMap = ser::Impossible<Vec<u8>, Error>;
+
+    type SerializeStruct = ser::Impossible<Vec<u8>, Error>;
+
+    type SerializeStructVariant = ser::Impossible<Vec<u8>, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Vec<u8>> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Vec<u8>> {
+        integer_key(v)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Vec<u8>> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Vec<u8>> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>> {
+        Ok(v.encode_utf8(&mut [0; 4]).as_bytes().to_vec())
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_some<T>(self, _: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Vec<u8>> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>> {
+        Ok(variant.as_bytes().to_vec())
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        if name == crate::raw::TOKEN || name == crate::ser::READER_BYTES_TOKEN {
+            return Err(Error::DictionaryKeyMustBeString);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        Ok(SerializeKeyBytes(Vec::with_capacity(len)))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::DictionaryKeyMustBeString)
+    }
+}